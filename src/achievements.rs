@@ -0,0 +1,145 @@
+//! Achievements defined in assets/achievements.ron, unlocked by gameplay
+//! events and announced with a toast in the corner of the screen.
+
+use std::collections::{HashSet, VecDeque};
+
+use cgmath::MetricSpace;
+use serde::Deserialize;
+
+use crate::{events::{EventBus, GameEvent, Subscription}, overlay::Overlay, resources};
+
+/// What has to happen to unlock an achievement.
+#[derive(Clone, Debug, Deserialize)]
+enum Condition {
+    Submerged,
+    /// Be below this height.
+    Depth(f32),
+    TorchLit,
+    Nightfall,
+    /// Cover this distance in one session.
+    Travel(f32),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Achievement {
+    /// Stored in the save file, so it shouldn't change.
+    id: String,
+    name: String,
+    description: String,
+    condition: Condition,
+}
+
+#[derive(Deserialize)]
+struct AchievementList {
+    achievements: Vec<Achievement>,
+}
+
+/// A recently unlocked achievement being announced.
+struct Toast {
+    name: String,
+    description: String,
+    age: f32,
+}
+
+pub struct Achievements {
+    achievements: Vec<Achievement>,
+    unlocked: HashSet<String>,
+    events: Subscription,
+    /// Distance travelled this session, for `Travel` conditions.
+    travelled: f32,
+    /// Waiting to be shown, oldest first. Only the first is on screen.
+    toasts: VecDeque<Toast>,
+}
+
+impl Achievements {
+    const TOAST_TIME: f32 = 4.0;
+    const TOAST_FADE: f32 = 0.5;
+    const TEXT_SCALE: f32 = 2.0;
+
+    /// Loads the achievement list. `unlocked` holds ids from the save file.
+    pub async fn load(file_name: &str, unlocked: &[String], events: &EventBus) -> anyhow::Result<Self> {
+        let text = resources::load_string(file_name).await?;
+        let list: AchievementList = ron::from_str(&text)?;
+        Ok(Self {
+            achievements: list.achievements,
+            unlocked: unlocked.iter().cloned().collect(),
+            events: events.subscribe(),
+            travelled: 0.0,
+            toasts: VecDeque::new(),
+        })
+    }
+
+    /// Ids of every unlocked achievement, for the save file.
+    pub fn unlocked(&self) -> Vec<String> {
+        let mut ids: Vec<_> = self.unlocked.iter().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Checks this frame's events and ages the toast on screen. Returns
+    /// whether anything was unlocked, so the save can be written.
+    pub fn update(&mut self, delta_time: f32) -> bool {
+        let mut unlocked_any = false;
+        let events: Vec<_> = self.events.drain().collect();
+        for event in events {
+            if let GameEvent::PlayerMoved { from, to } = event {
+                self.travelled += from.distance(to);
+            }
+            for achievement in &self.achievements {
+                if self.unlocked.contains(&achievement.id) || !self.meets(&achievement.condition, &event) {
+                    continue;
+                }
+                tracing::info!("Achievement unlocked: {}", achievement.name);
+                self.unlocked.insert(achievement.id.clone());
+                self.toasts.push_back(Toast {
+                    name: achievement.name.clone(),
+                    description: achievement.description.clone(),
+                    age: 0.0,
+                });
+                unlocked_any = true;
+            }
+        }
+
+        if let Some(toast) = self.toasts.front_mut() {
+            toast.age += delta_time;
+            if toast.age > Self::TOAST_TIME {
+                self.toasts.pop_front();
+            }
+        }
+        unlocked_any
+    }
+
+    fn meets(&self, condition: &Condition, event: &GameEvent) -> bool {
+        match (condition, event) {
+            (Condition::Submerged, GameEvent::Submerged { submerged }) => *submerged,
+            (Condition::Depth(height), GameEvent::PlayerMoved { to, .. }) => to.y < *height,
+            (Condition::TorchLit, GameEvent::TorchToggled { held }) => *held,
+            (Condition::Nightfall, GameEvent::DaylightChanged { day }) => !*day,
+            (Condition::Travel(distance), GameEvent::PlayerMoved { .. }) => self.travelled >= *distance,
+            _ => false,
+        }
+    }
+
+    /// Shows the current toast in the bottom right, sliding in and fading out.
+    pub fn draw(&self, overlay: &mut Overlay, size: winit::dpi::PhysicalSize<u32>) {
+        let Some(toast) = self.toasts.front() else {
+            return;
+        };
+        let alpha = (toast.age / Self::TOAST_FADE)
+            .min((Self::TOAST_TIME - toast.age) / Self::TOAST_FADE)
+            .clamp(0.0, 1.0);
+
+        let line_height = Overlay::line_height(Self::TEXT_SCALE) + 4.0;
+        let title = format!("Achievement: {}", toast.name);
+        // Glyphs are square, so a character is as wide as a line is high
+        let width = title.len().max(toast.description.len()) as f32 * Overlay::line_height(Self::TEXT_SCALE) + 16.0;
+        let height = line_height * 2.0 + 12.0;
+        let x = size.width as f32 - width - 16.0;
+        let y = size.height as f32 - height - 16.0 + (1.0 - alpha) * 20.0;
+
+        overlay.rect(x, y, width, height, [0.05, 0.05, 0.1, 0.85 * alpha]);
+        overlay.rect(x, y, 4.0, height, [1.0, 0.8, 0.2, alpha]);
+        overlay.text(x + 10.0, y + 6.0, &title, Self::TEXT_SCALE, [1.0, 0.85, 0.3, alpha]);
+        overlay.text(x + 10.0, y + 6.0 + line_height, &toast.description, Self::TEXT_SCALE, [0.9, 0.9, 0.9, alpha]);
+    }
+}
@@ -0,0 +1,162 @@
+//! Automatic quality: lowers the render scale when frames take too long and
+//! raises it again when there's time to spare, to hold a target frame rate.
+//! With vsync the frame rate can't go above the display's, so spare time is
+//! judged from the work time: how long a frame took, less the time spent
+//! waiting for the swapchain.
+//! It only moves between `GraphicsSettings::min_render_scale` and the chosen
+//! render scale, and never changes the settings themselves.
+
+use crate::settings::GraphicsSettings;
+
+pub struct AutoQuality {
+    /// The render scale currently in use, when enabled.
+    scale: f32,
+    /// Smoothed seconds per frame.
+    frame_time: Option<f32>,
+    /// Smoothed seconds of work per frame.
+    work_time: Option<f32>,
+    /// Seconds the frame rate has been below the target (negative), or the
+    /// work has left time to spare (positive).
+    pressure: f32,
+    /// Seconds until the scale can change again.
+    cooldown: f32,
+}
+
+impl AutoQuality {
+    /// How much the render scale changes in one step.
+    const STEP: f32 = 0.1;
+    /// How quickly the smoothed frame time follows new frames, per second.
+    const SMOOTHING: f32 = 4.0;
+    /// Below this fraction of the target frame rate, the scale goes down.
+    const SLOW: f32 = 0.9;
+    /// When work takes less than this fraction of the target frame time, the
+    /// scale goes up. The gap between this and `SLOW` is what keeps the
+    /// scale from oscillating.
+    const SPARE: f32 = 0.7;
+    /// Seconds the frame rate has to stay low before stepping down.
+    const DOWN_DELAY: f32 = 1.0;
+    /// Seconds there has to be time to spare before stepping up; longer, so
+    /// a brief quiet moment doesn't undo a needed step down.
+    const UP_DELAY: f32 = 3.0;
+    /// Seconds to wait after a step, so its effect shows in the frame time.
+    const COOLDOWN: f32 = 1.0;
+
+    pub fn new(settings: &GraphicsSettings) -> Self {
+        Self { scale: settings.render_scale, frame_time: None, work_time: None, pressure: 0.0, cooldown: 0.0 }
+    }
+
+    /// The render scale to draw at.
+    pub fn scale(&self, settings: &GraphicsSettings) -> f32 {
+        if settings.auto_quality {
+            self.scale.clamp(settings.min_render_scale.min(settings.render_scale), settings.render_scale)
+        } else {
+            settings.render_scale
+        }
+    }
+
+    /// Takes the real time the last frame took, and how much of it was work
+    /// rather than waiting for vsync. Shouldn't be called while the frame
+    /// rate is limited for some other reason, like being unfocused.
+    pub fn update(&mut self, frame_time: f32, work_time: f32, settings: &GraphicsSettings) {
+        if !settings.auto_quality || frame_time <= 0.0 {
+            self.scale = settings.render_scale;
+            self.frame_time = None;
+            self.work_time = None;
+            self.pressure = 0.0;
+            return;
+        }
+
+        let smoothing = (Self::SMOOTHING * frame_time).min(1.0);
+        let smooth = |average: Option<f32>, value: f32| match average {
+            Some(average) => average + (value - average) * smoothing,
+            None => value,
+        };
+        let average = smooth(self.frame_time, frame_time);
+        let work = smooth(self.work_time, work_time);
+        self.frame_time = Some(average);
+        self.work_time = Some(work);
+
+        self.cooldown -= frame_time;
+        if self.cooldown > 0.0 {
+            return;
+        }
+
+        let fps = 1.0 / average;
+        if fps < settings.target_fps * Self::SLOW {
+            self.pressure = self.pressure.min(0.0) - frame_time;
+        } else if work < Self::SPARE / settings.target_fps {
+            self.pressure = self.pressure.max(0.0) + frame_time;
+        } else {
+            self.pressure = 0.0;
+        }
+
+        let scale = self.scale(settings);
+        let step = if self.pressure <= -Self::DOWN_DELAY {
+            -Self::STEP
+        } else if self.pressure >= Self::UP_DELAY {
+            Self::STEP
+        } else {
+            return;
+        };
+        self.scale = scale + step;
+        self.scale = self.scale(settings);
+        if self.scale != scale {
+            tracing::debug!("Auto quality: render scale {:.2} at {:.0} FPS", self.scale, fps);
+        }
+        self.pressure = 0.0;
+        self.cooldown = Self::COOLDOWN;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFRESH: f32 = 1.0 / 60.0;
+
+    fn settings() -> GraphicsSettings {
+        GraphicsSettings { auto_quality: true, render_scale: 1.0, min_render_scale: 0.5, target_fps: 60.0, ..GraphicsSettings::default() }
+    }
+
+    /// Runs `seconds` of identical frames.
+    fn run(quality: &mut AutoQuality, seconds: f32, frame_time: f32, work_time: f32, settings: &GraphicsSettings) {
+        for _ in 0..(seconds / frame_time) as usize {
+            quality.update(frame_time, work_time, settings);
+        }
+    }
+
+    #[test]
+    fn recovers_after_a_spike_under_vsync() {
+        let settings = settings();
+        let mut quality = AutoQuality::new(&settings);
+        run(&mut quality, 5.0, 1.0 / 20.0, 1.0 / 20.0, &settings);
+        assert!(quality.scale(&settings) < 1.0, "a slow spike should lower the scale");
+
+        // Vsync holds the frame rate at exactly the target, with most of
+        // each frame spent waiting
+        run(&mut quality, 30.0, REFRESH, REFRESH * 0.3, &settings);
+        assert_eq!(quality.scale(&settings), 1.0);
+    }
+
+    #[test]
+    fn holds_when_work_fills_the_frame() {
+        let settings = settings();
+        let mut quality = AutoQuality::new(&settings);
+        run(&mut quality, 5.0, 1.0 / 20.0, 1.0 / 20.0, &settings);
+        let lowered = quality.scale(&settings);
+
+        // On target, but without enough spare time to risk a step up
+        run(&mut quality, 30.0, REFRESH, REFRESH * 0.9, &settings);
+        assert_eq!(quality.scale(&settings), lowered);
+    }
+
+    #[test]
+    fn disabled_uses_the_render_scale() {
+        let mut settings = settings();
+        let mut quality = AutoQuality::new(&settings);
+        run(&mut quality, 5.0, 1.0 / 20.0, 1.0 / 20.0, &settings);
+        settings.auto_quality = false;
+        quality.update(REFRESH, REFRESH, &settings);
+        assert_eq!(quality.scale(&settings), settings.render_scale);
+    }
+}
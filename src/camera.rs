@@ -1,10 +1,14 @@
-use cgmath::{EuclideanSpace, Quaternion, Rad, Rotation, Rotation3, Vector3, Zero};
+use cgmath::{EuclideanSpace, Quaternion, Rad, Rotation3, Vector3, Zero};
 use winit::{dpi::PhysicalSize, event::{ElementState, KeyEvent, WindowEvent}, keyboard::{KeyCode, PhysicalKey}};
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
     view_proj: [[f32; 4]; 4],
+    /// Used to reconstruct world positions from depth
+    inv_view_proj: [[f32; 4]; 4],
+    /// w is unused
+    position: [f32; 4],
 }
 
 impl CameraUniform {
@@ -12,11 +16,17 @@ impl CameraUniform {
         use cgmath::SquareMatrix;
         Self {
             view_proj: cgmath::Matrix4::identity().into(),
+            inv_view_proj: cgmath::Matrix4::identity().into(),
+            position: [0.0; 4],
         }
     }
 
     pub fn update_view_proj(&mut self, camera: &Camera) {
-        self.view_proj = camera.build_view_projection_matrix().into();
+        use cgmath::SquareMatrix;
+        let view_proj = camera.build_view_projection_matrix();
+        self.view_proj = view_proj.into();
+        self.inv_view_proj = view_proj.invert().unwrap_or(cgmath::Matrix4::identity()).into();
+        self.position = camera.eye.to_homogeneous().into();
     }
 }
 
@@ -49,16 +59,37 @@ impl Camera {
         self.aspect = aspect;
     }
 
-    fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+    pub fn position(&self) -> cgmath::Point3<f32> {
+        self.eye
+    }
+
+    /// The world-space direction pointing to the right of the screen.
+    pub fn right(&self) -> Vector3<f32> {
+        self.rotation.conjugate() * Vector3::unit_x()
+    }
+
+    /// The world-space direction pointing to the top of the screen.
+    pub fn up(&self) -> Vector3<f32> {
+        self.rotation.conjugate() * Vector3::unit_y()
+    }
+
+    /// The world-space direction the camera is looking.
+    pub fn forward(&self) -> Vector3<f32> {
+        self.rotation.conjugate() * -Vector3::unit_z()
+    }
+
+    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
         let view = cgmath::Matrix4::from(self.rotation) * cgmath::Matrix4::from_translation(-self.eye.to_vec());
         let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
 
-        return OPENGL_TO_WGPU_MATRIX * proj * view;
+        OPENGL_TO_WGPU_MATRIX * proj * view
     }
 }
 
 pub struct CameraController {
     speed: f32,
+    /// Scales `speed`, for status effects.
+    pub speed_multiplier: f32,
 
     yaw: f32,
     pitch: f32,
@@ -75,6 +106,7 @@ impl CameraController {
     pub fn new(speed: f32) -> Self {
         Self {
             speed,
+            speed_multiplier: 1.0,
             
             yaw: 0.0,
             pitch: 0.0,
@@ -145,6 +177,22 @@ impl CameraController {
         }
     }
 
+    /// Turns the camera to the right.
+    pub fn turn(&mut self, yaw: f32) {
+        self.yaw += yaw;
+    }
+
+    /// Forgets held keys. Their releases go elsewhere once the window loses
+    /// focus, so without this the camera would keep moving.
+    pub fn release_keys(&mut self) {
+        self.is_forward_pressed = false;
+        self.is_backward_pressed = false;
+        self.is_left_pressed = false;
+        self.is_right_pressed = false;
+        self.is_up_pressed = false;
+        self.is_down_pressed = false;
+    }
+
     pub fn update_camera(&self, camera: &mut Camera, delta_time: f32) {
         use cgmath::InnerSpace;
 
@@ -175,7 +223,7 @@ impl CameraController {
         }
 
         if movement.magnitude() > 0.0 {
-            movement = movement.normalize() * self.speed * delta_time;
+            movement = movement.normalize() * self.speed * self.speed_multiplier * delta_time;
             camera.eye += movement;
         }
 
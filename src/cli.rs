@@ -0,0 +1,33 @@
+//! Command-line launch options.
+
+use clap::Parser;
+
+use crate::gpu::GpuOptions;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "A voxel game")]
+pub struct Args {
+    /// The GPU to use, as an index from --list-gpus or part of its name
+    #[arg(long, value_name = "GPU")]
+    pub gpu: Option<String>,
+    /// Print every GPU and exit
+    #[arg(long)]
+    pub list_gpus: bool,
+    /// Start with a minimal renderer, for drivers that struggle with the
+    /// full one. Also used automatically after a crash
+    #[arg(long)]
+    pub safe_mode: bool,
+    /// Output HDR (scRGB) if the display supports it
+    #[arg(long)]
+    pub hdr: bool,
+}
+
+impl Args {
+    pub fn gpu_options(&self) -> GpuOptions {
+        GpuOptions {
+            adapter: self.gpu.clone(),
+            safe_mode: self.safe_mode,
+            hdr: self.hdr,
+        }
+    }
+}
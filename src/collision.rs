@@ -0,0 +1,81 @@
+use cgmath::{Point3, Vector3};
+
+/// An axis-aligned bounding box in world space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Builds the smallest box containing every point, or `None` if there are no points.
+    pub fn from_points(points: impl IntoIterator<Item = Point3<f32>>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, p| Self {
+            min: Point3::new(aabb.min.x.min(p.x), aabb.min.y.min(p.y), aabb.min.z.min(p.z)),
+            max: Point3::new(aabb.max.x.max(p.x), aabb.max.y.max(p.y), aabb.max.z.max(p.z)),
+        }))
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x < other.max.x && self.max.x > other.min.x &&
+        self.min.y < other.max.y && self.max.y > other.min.y &&
+        self.min.z < other.max.z && self.max.z > other.min.z
+    }
+
+    /// How far along a ray from `origin` in `direction` it first enters the
+    /// box, or `None` if it misses. It's 0 if `origin` is inside.
+    pub fn ray_distance(&self, origin: Point3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, f32::INFINITY);
+        for axis in 0..3 {
+            // Dividing by a zero component gives infinities, which still
+            // compare correctly
+            let inverse = 1.0 / direction[axis];
+            let a = (self.min[axis] - origin[axis]) * inverse;
+            let b = (self.max[axis] - origin[axis]) * inverse;
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        (near <= far).then_some(near)
+    }
+
+    /// The eight corners, ordered so that bit 0 selects x, bit 1 selects y and bit 2 selects z.
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        std::array::from_fn(|i| Point3::new(
+            if i & 1 == 0 { self.min.x } else { self.max.x },
+            if i & 2 == 0 { self.min.y } else { self.max.y },
+            if i & 4 == 0 { self.min.z } else { self.max.z },
+        ))
+    }
+}
+
+/// The outcome of one collision test, kept around so it can be visualized.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CollisionResult {
+    Clear,
+    Touching,
+}
+
+/// Every box tested against the player during the last update, with its result.
+#[derive(Default)]
+pub struct CollisionLog {
+    pub tests: Vec<(Aabb, CollisionResult)>,
+}
+
+impl CollisionLog {
+    pub fn clear(&mut self) {
+        self.tests.clear();
+    }
+
+    /// Tests `a` against `b`, recording `b` and the result.
+    pub fn test(&mut self, a: &Aabb, b: &Aabb) -> CollisionResult {
+        let result = if a.intersects(b) { CollisionResult::Touching } else { CollisionResult::Clear };
+        self.tests.push((*b, result));
+        result
+    }
+}
@@ -0,0 +1,98 @@
+//! Writes a crash report when the game panics, with what's needed to make
+//! sense of it: the panic and backtrace, the GPU, the settings and the most
+//! recent log lines. The report goes in `crash-reports/`. Its path is
+//! printed after the usual panic message, and the next launch shows it on
+//! the main menu; see `session`.
+
+use std::sync::Mutex;
+
+use crate::settings::{GameplaySettings, GraphicsSettings};
+
+/// What the game was running with, kept up to date for the report.
+struct Context {
+    adapter: Option<wgpu::AdapterInfo>,
+    settings: Option<(GraphicsSettings, GameplaySettings)>,
+}
+
+static CONTEXT: Mutex<Context> = Mutex::new(Context { adapter: None, settings: None });
+
+#[cfg(not(target_arch = "wasm32"))]
+const DIRECTORY: &str = "crash-reports";
+/// How many log lines a report includes.
+#[cfg(not(target_arch = "wasm32"))]
+const LOG_LINES: usize = 200;
+
+fn context() -> std::sync::MutexGuard<'static, Context> {
+    CONTEXT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn set_adapter(info: wgpu::AdapterInfo) {
+    context().adapter = Some(info);
+}
+
+pub fn set_settings(graphics: GraphicsSettings, gameplay: GameplaySettings) {
+    context().settings = Some((graphics, gameplay));
+}
+
+/// Installs the panic hook. The default hook still runs first, so the panic
+/// is printed as usual. The web has nowhere to write a report, so it keeps
+/// the default hook.
+pub fn install() {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            default_hook(info);
+            match write_report(info) {
+                Ok(path) => {
+                    eprintln!("The game crashed. A crash report was written to {}", path.display());
+                    if let Err(e) = crate::session::record_report(&path) {
+                        eprintln!("Failed to record the crash report for the next launch: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("The game crashed, and writing a crash report failed: {}", e),
+            }
+        }));
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_report(info: &std::panic::PanicHookInfo) -> anyhow::Result<std::path::PathBuf> {
+    use std::fmt::Write;
+
+    let backtrace = std::backtrace::Backtrace::force_capture();
+    let time = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+
+    let mut report = String::new();
+    writeln!(report, "VoxelGame {} crashed at {} (seconds since the Unix epoch)", env!("CARGO_PKG_VERSION"), time)?;
+    writeln!(report, "\n{}", info)?;
+    writeln!(report, "\nBacktrace:\n{}", backtrace)?;
+
+    // A panic while the context is locked would deadlock here
+    match CONTEXT.try_lock() {
+        Ok(context) => {
+            match &context.adapter {
+                Some(adapter) => writeln!(report, "\nGPU: {:#?}", adapter)?,
+                None => writeln!(report, "\nGPU: not chosen yet")?,
+            }
+            if let Some((graphics, gameplay)) = &context.settings {
+                writeln!(report, "\nGraphics settings: {:#?}\nGameplay settings: {:#?}", graphics, gameplay)?;
+            }
+        }
+        Err(_) => writeln!(report, "\nThe GPU and settings are unavailable")?,
+    }
+
+    writeln!(report, "\nLast {} log lines:", LOG_LINES)?;
+    match crate::logging::try_recent_lines(tracing::Level::TRACE, LOG_LINES) {
+        Some(lines) => for line in lines {
+            writeln!(report, "{:>5} {}: {}", line.level, line.spans, line.message)?;
+        },
+        // Waiting could deadlock if this thread panicked while logging
+        None => writeln!(report, "(unavailable: the log was locked)")?,
+    }
+
+    std::fs::create_dir_all(DIRECTORY)?;
+    let path = std::path::Path::new(DIRECTORY).join(format!("crash-{}.txt", time));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
@@ -0,0 +1,248 @@
+//! Immediate-mode debug drawing. Any system can queue shapes during update
+//! with the `draw_*` functions; everything queued is drawn by the next frame
+//! in one line batch and one billboard batch, then discarded.
+
+use std::{mem, sync::Mutex};
+
+use cgmath::{InnerSpace, MetricSpace, Point3, Vector3};
+
+use crate::{camera::Camera, collision::Aabb, font, memory::MemoryUsage, model::Vertex, texture};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+impl DebugVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+}
+
+impl Vertex for DebugVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<DebugVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &DebugVertex::ATTRIBS
+        }
+    }
+}
+
+struct DebugText {
+    position: Point3<f32>,
+    text: String,
+    color: [f32; 3],
+}
+
+struct DebugQueue {
+    lines: Vec<DebugVertex>,
+    texts: Vec<DebugText>,
+}
+
+static QUEUE: Mutex<DebugQueue> = Mutex::new(DebugQueue { lines: Vec::new(), texts: Vec::new() });
+
+fn queue() -> std::sync::MutexGuard<'static, DebugQueue> {
+    // A panic while holding the lock can't leave the queue in a bad state, so ignore poisoning
+    QUEUE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn draw_line(a: Point3<f32>, b: Point3<f32>, color: [f32; 3]) {
+    let mut queue = queue();
+    queue.lines.push(DebugVertex { position: a.into(), color });
+    queue.lines.push(DebugVertex { position: b.into(), color });
+}
+
+pub fn draw_aabb(aabb: &Aabb, color: [f32; 3]) {
+    let corners = aabb.corners();
+    // Each edge joins two corners whose indices differ in exactly one bit
+    for i in 0..8 {
+        for axis in [1, 2, 4] {
+            if i & axis == 0 {
+                draw_line(corners[i], corners[i | axis], color);
+            }
+        }
+    }
+}
+
+/// Draws a sphere as three axis-aligned circles.
+#[allow(unused)]
+pub fn draw_sphere(center: Point3<f32>, radius: f32, color: [f32; 3]) {
+    const SEGMENTS: usize = 24;
+
+    let axes = [
+        (Vector3::unit_x(), Vector3::unit_y()),
+        (Vector3::unit_y(), Vector3::unit_z()),
+        (Vector3::unit_z(), Vector3::unit_x()),
+    ];
+    for (u, v) in axes {
+        let point = |i: usize| {
+            let angle = i as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        };
+        for i in 0..SEGMENTS {
+            draw_line(point(i), point(i + 1), color);
+        }
+    }
+}
+
+/// Draws camera-facing text anchored at its top-left corner. Text stays the
+/// same size on screen regardless of distance.
+pub fn draw_text_3d(position: Point3<f32>, text: &str, color: [f32; 3]) {
+    queue().texts.push(DebugText { position, text: text.to_string(), color });
+}
+
+/// Uploads and draws everything queued with the `draw_*` functions.
+pub struct DebugDraw {
+    line_buffer: wgpu::Buffer,
+    num_line_vertices: u32,
+    billboard_buffer: wgpu::Buffer,
+    num_billboard_vertices: u32,
+    line_pipeline: wgpu::RenderPipeline,
+    billboard_pipeline: wgpu::RenderPipeline,
+}
+
+impl DebugDraw {
+    const INITIAL_CAPACITY: usize = 1024;
+    /// World-space size of a font pixel per unit of distance from the camera.
+    const TEXT_SCALE: f32 = 0.002;
+
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/debugShader.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Draw Pipeline Layout"),
+            bind_group_layouts: &[
+                camera_bind_group_layout
+            ],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |label: &str, topology: wgpu::PrimitiveTopology| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[
+                        DebugVertex::desc()
+                    ],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                // Debug geometry is depth-tested against the scene but never occludes anything itself
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None
+            })
+        };
+
+        Self {
+            line_buffer: Self::create_vertex_buffer(device, "Debug Line Vertex Buffer", Self::INITIAL_CAPACITY),
+            num_line_vertices: 0,
+            billboard_buffer: Self::create_vertex_buffer(device, "Debug Billboard Vertex Buffer", Self::INITIAL_CAPACITY),
+            num_billboard_vertices: 0,
+            line_pipeline: create_pipeline("Debug Line Pipeline", wgpu::PrimitiveTopology::LineList),
+            billboard_pipeline: create_pipeline("Debug Billboard Pipeline", wgpu::PrimitiveTopology::TriangleList),
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, label: &str, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (capacity * mem::size_of::<DebugVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Writes `vertices` into `buffer`, growing it first if needed, and returns the vertex count.
+    fn upload(device: &wgpu::Device, queue: &wgpu::Queue, buffer: &mut wgpu::Buffer, label: &str, vertices: &[DebugVertex]) -> u32 {
+        let needed = mem::size_of_val(vertices) as wgpu::BufferAddress;
+        if needed > buffer.size() {
+            *buffer = Self::create_vertex_buffer(device, label, vertices.len().next_power_of_two());
+        }
+        queue.write_buffer(buffer, 0, bytemuck::cast_slice(vertices));
+        vertices.len() as u32
+    }
+
+    /// Expands queued text into camera-facing quads, one per lit font pixel.
+    fn build_billboards(texts: &[DebugText], camera: &Camera) -> Vec<DebugVertex> {
+        let mut vertices = Vec::new();
+        let (right, down) = (camera.right().normalize(), -camera.up().normalize());
+        for text in texts {
+            let pixel = camera.position().distance(text.position) * Self::TEXT_SCALE;
+            let (right, down) = (right * pixel, down * pixel);
+            font::for_each_pixel(&text.text, |x, y| {
+                let corner = text.position + right * x as f32 + down * y as f32;
+                let quad = [corner, corner + right, corner + right + down, corner + down];
+                for i in [0, 1, 2, 0, 2, 3] {
+                    vertices.push(DebugVertex { position: quad[i].into(), color: text.color });
+                }
+            });
+        }
+        vertices
+    }
+
+    /// Uploads everything queued since the last frame and clears the queue.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, camera: &Camera) {
+        let (lines, texts) = {
+            let mut debug_queue = self::queue();
+            (mem::take(&mut debug_queue.lines), mem::take(&mut debug_queue.texts))
+        };
+
+        self.num_line_vertices = Self::upload(device, queue, &mut self.line_buffer, "Debug Line Vertex Buffer", &lines);
+        let billboards = Self::build_billboards(&texts, camera);
+        self.num_billboard_vertices = Self::upload(device, queue, &mut self.billboard_buffer, "Debug Billboard Vertex Buffer", &billboards);
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        if self.num_line_vertices > 0 {
+            render_pass.set_pipeline(&self.line_pipeline);
+            render_pass.set_vertex_buffer(0, self.line_buffer.slice(..));
+            render_pass.draw(0..self.num_line_vertices, 0..1);
+        }
+        if self.num_billboard_vertices > 0 {
+            render_pass.set_pipeline(&self.billboard_pipeline);
+            render_pass.set_vertex_buffer(0, self.billboard_buffer.slice(..));
+            render_pass.draw(0..self.num_billboard_vertices, 0..1);
+        }
+    }
+
+    pub fn memory(&self) -> MemoryUsage {
+        MemoryUsage::buffer(&self.line_buffer) + MemoryUsage::buffer(&self.billboard_buffer)
+    }
+}
@@ -0,0 +1,174 @@
+//! Data that changes between draws in a pass, like where a mesh sits in the
+//! world. It's given to shaders as push constants where the GPU supports
+//! them, and otherwise from a uniform buffer bound at a different offset for
+//! each draw, so nothing has to be written between draws.
+//!
+//! Shaders read it as `draw`, declared in an `#ifdef PUSH_CONSTANTS` block;
+//! see the G-buffer shader.
+
+use std::mem;
+
+use crate::{memory::MemoryUsage, shader_preprocessor::ShaderDefines};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawUniform {
+    /// Added to every vertex position, so meshes can be built around their
+    /// own origin.
+    pub offset: [f32; 3],
+    /// How far the mesh has faded in, from 0 to 1. It's dithered rather than
+    /// blended, so it works in the G-buffer.
+    pub fade: f32,
+    /// Written to the G-buffer as the object ID: the draw's index in
+    /// `prepare` plus one, so 0 means nothing was drawn. See `ObjectPicker`.
+    pub id: u32,
+    pub _padding: [u32; 3],
+}
+
+const DRAW_SIZE: wgpu::BufferAddress = mem::size_of::<DrawUniform>() as wgpu::BufferAddress;
+const STAGES: wgpu::ShaderStages = wgpu::ShaderStages::VERTEX_FRAGMENT;
+
+enum Storage {
+    PushConstants,
+    /// Every draw's data in one buffer, `stride` bytes apart.
+    Uniform {
+        layout: wgpu::BindGroupLayout,
+        buffer: wgpu::Buffer,
+        bind_group: wgpu::BindGroup,
+        stride: wgpu::BufferAddress,
+    },
+}
+
+pub struct DrawData {
+    storage: Storage,
+    /// This frame's draws, in the order they were given to `prepare`.
+    draws: Vec<DrawUniform>,
+}
+
+impl DrawData {
+    /// Draws the uniform buffer has room for before it has to grow.
+    const INITIAL_CAPACITY: u64 = 64;
+
+    /// Uses push constants if `push_constants` is true; the device must have
+    /// been created with `Features::PUSH_CONSTANTS` for that.
+    pub fn new(device: &wgpu::Device, push_constants: bool) -> Self {
+        if push_constants {
+            return Self { storage: Storage::PushConstants, draws: Vec::new() };
+        }
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Draw Data Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: STAGES,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(DRAW_SIZE),
+                    },
+                    count: None,
+                }
+            ]
+        });
+        // Dynamic offsets have to be aligned, which is usually far more than one draw needs
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+        let stride = DRAW_SIZE.div_ceil(alignment) * alignment;
+        let (buffer, bind_group) = Self::create_buffer(device, &layout, stride * Self::INITIAL_CAPACITY);
+        Self { storage: Storage::Uniform { layout, buffer, bind_group, stride }, draws: Vec::new() }
+    }
+
+    fn create_buffer(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        size: wgpu::BufferAddress
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Draw Data Buffer"),
+            size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Draw Data Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(DRAW_SIZE),
+                    }),
+                }
+            ],
+        });
+        (buffer, bind_group)
+    }
+
+    /// Selects the shader code that matches how draw data is stored.
+    pub fn defines(&self) -> ShaderDefines {
+        ShaderDefines::new().set("PUSH_CONSTANTS", matches!(self.storage, Storage::PushConstants))
+    }
+
+    /// The layout pipelines need at the group they read draw data from, if
+    /// draw data isn't in push constants.
+    pub fn bind_group_layout(&self) -> Option<&wgpu::BindGroupLayout> {
+        match &self.storage {
+            Storage::PushConstants => None,
+            Storage::Uniform { layout, .. } => Some(layout),
+        }
+    }
+
+    /// Push constant ranges for pipeline layouts; empty without push constants.
+    pub fn push_constant_ranges(&self) -> Vec<wgpu::PushConstantRange> {
+        match self.storage {
+            Storage::PushConstants => vec![wgpu::PushConstantRange { stages: STAGES, range: 0..DRAW_SIZE as u32 }],
+            Storage::Uniform { .. } => Vec::new(),
+        }
+    }
+
+    /// Sets this frame's draws. Draw `i` in any pass reads `draws[i]`.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, draws: &[DrawUniform]) {
+        self.draws.clear();
+        self.draws.extend_from_slice(draws);
+
+        if let Storage::Uniform { layout, buffer, bind_group, stride } = &mut self.storage {
+            if draws.is_empty() {
+                return;
+            }
+            let needed = *stride * draws.len() as wgpu::BufferAddress;
+            if needed > buffer.size() {
+                (*buffer, *bind_group) = Self::create_buffer(device, layout, needed.next_power_of_two());
+            }
+
+            let mut bytes = vec![0; needed as usize];
+            for (i, draw) in draws.iter().enumerate() {
+                let start = i * *stride as usize;
+                bytes[start..start + DRAW_SIZE as usize].copy_from_slice(bytemuck::bytes_of(draw));
+            }
+            queue.write_buffer(buffer, 0, &bytes);
+        }
+    }
+
+    /// Makes draw `index` the one the next draw call reads. Without push
+    /// constants this binds the draw's slice of the buffer at `group`.
+    pub fn bind(&self, render_pass: &mut wgpu::RenderPass, group: u32, index: usize) {
+        match &self.storage {
+            Storage::PushConstants => {
+                render_pass.set_push_constants(STAGES, 0, bytemuck::bytes_of(&self.draws[index]));
+            }
+            Storage::Uniform { bind_group, stride, .. } => {
+                render_pass.set_bind_group(group, bind_group, &[(index as wgpu::BufferAddress * stride) as u32]);
+            }
+        }
+    }
+
+    /// Nothing with push constants, and the whole buffer otherwise.
+    pub fn memory(&self) -> MemoryUsage {
+        match &self.storage {
+            Storage::PushConstants => MemoryUsage::default(),
+            Storage::Uniform { buffer, .. } => MemoryUsage::buffer(buffer),
+        }
+    }
+}
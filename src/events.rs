@@ -0,0 +1,59 @@
+//! Gameplay events. Systems publish what happened to the bus, and systems
+//! that care subscribe and read the events in their own update, so the
+//! publisher doesn't need to know who's listening.
+
+use std::sync::{mpsc, Arc, Mutex};
+
+use cgmath::Point3;
+
+#[derive(Clone, Debug)]
+pub enum GameEvent {
+    /// The world finished loading and is shown for the first time.
+    WorldLoaded,
+    /// The player moved this frame.
+    PlayerMoved { from: Point3<f32>, to: Point3<f32> },
+    /// The player's eyes went under or came back above the water.
+    Submerged { submerged: bool },
+    TorchToggled { held: bool },
+    /// The sun rose or set.
+    DaylightChanged { day: bool },
+}
+
+/// A handle for publishing events. Clones publish to the same subscribers,
+/// so it can be handed to background tasks.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<GameEvent>>>>,
+}
+
+impl EventBus {
+    pub fn publish(&self, event: GameEvent) {
+        // Moving every frame would drown out everything else in the log
+        if !matches!(event, GameEvent::PlayerMoved { .. }) {
+            tracing::debug!("Event: {:?}", event);
+        }
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        // Sending only fails once the subscriber is dropped, so forget it
+        subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// Every event published from now on is also queued for the returned
+    /// subscription.
+    pub fn subscribe(&self) -> Subscription {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner()).push(sender);
+        Subscription { receiver }
+    }
+}
+
+/// Events queued for one subscriber.
+pub struct Subscription {
+    receiver: mpsc::Receiver<GameEvent>,
+}
+
+impl Subscription {
+    /// Takes every event published since the last call, oldest first.
+    pub fn drain(&self) -> impl Iterator<Item = GameEvent> + '_ {
+        self.receiver.try_iter()
+    }
+}
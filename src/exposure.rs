@@ -0,0 +1,364 @@
+//! Auto exposure and tonemapping, the last step between the HDR scene and
+//! the screen. The scene's average luminance is found by reducing its log
+//! luminance down a mip chain, and the exposure drifts towards it over time
+//! like an eye adjusting when moving between daylight and a dark cave.
+
+use crate::{memory::MemoryUsage, pipeline_cache::create_shader_module, render_cache::RenderCache, settings::GraphicsSettings, shader_preprocessor::ShaderDefines, texture::Texture};
+
+/// Size of the first luminance mip. The scene is sampled down to this before
+/// being averaged, so it needs to be a power of two.
+const LUMINANCE_SIZE: u32 = 256;
+const LUMINANCE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+/// How quickly exposure adapts, in e-foldings per second.
+const ADAPTATION_SPEED: f32 = 1.5;
+/// The brightness an average scene is exposed to.
+const KEY: f32 = 0.4;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ExposureSettings {
+    key: f32,
+    min_exposure: f32,
+    max_exposure: f32,
+    gamma: f32,
+    paper_white: f32,
+    peak_brightness: f32,
+    _padding: [f32; 2],
+}
+
+pub struct Exposure {
+    luminance_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    adapt_pipeline: wgpu::RenderPipeline,
+    tonemap_pipeline: wgpu::RenderPipeline,
+
+    source_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    /// Reads the HDR scene; recreated on resize.
+    scene_bind_group: wgpu::BindGroup,
+    luminance_texture: wgpu::Texture,
+    /// One view per mip of the luminance texture, largest first.
+    luminance_views: Vec<wgpu::TextureView>,
+    /// Reads each luminance mip, in the same order as `luminance_views`.
+    luminance_bind_groups: Vec<wgpu::BindGroup>,
+    adapted_texture: wgpu::Texture,
+    adapted_view: wgpu::TextureView,
+    adapted_bind_group: wgpu::BindGroup,
+    settings_buffer: wgpu::Buffer,
+    /// How far towards this frame's luminance the adapted value moves.
+    adaptation: f64,
+    /// Whether the adapted value has been written yet.
+    measured: bool,
+}
+
+impl Exposure {
+    /// With `hdr_output`, the scene is written to `format` as scRGB rather
+    /// than tonemapped to SDR.
+    pub fn new(
+        device: &wgpu::Device,
+        cache: &RenderCache,
+        format: wgpu::TextureFormat,
+        hdr_output: bool,
+        scene_texture: &Texture
+    ) -> anyhow::Result<Self> {
+        let source_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Exposure Source Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                }
+            ]
+        });
+        let adapted_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Exposure Adapted Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ]
+        });
+
+        let sampler = cache.linear_clamp_sampler();
+        let scene_bind_group = Self::create_source_bind_group(device, &source_layout, &sampler, &scene_texture.view);
+
+        let mip_level_count = LUMINANCE_SIZE.ilog2() + 1;
+        let luminance_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Luminance Texture"),
+            size: wgpu::Extent3d { width: LUMINANCE_SIZE, height: LUMINANCE_SIZE, depth_or_array_layers: 1 },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: LUMINANCE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let luminance_views: Vec<_> = (0..mip_level_count)
+            .map(|level| luminance_texture.create_view(&wgpu::TextureViewDescriptor {
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            }))
+            .collect();
+        let luminance_bind_groups = luminance_views.iter()
+            .map(|view| Self::create_source_bind_group(device, &source_layout, &sampler, view))
+            .collect();
+
+        let adapted_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Adapted Luminance Texture"),
+            size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: LUMINANCE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let adapted_view = adapted_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let settings_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Exposure Settings Buffer"),
+            size: std::mem::size_of::<ExposureSettings>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let adapted_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &adapted_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&adapted_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: settings_buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("Exposure Adapted Bind Group"),
+        });
+
+        let shader = create_shader_module(
+            device,
+            "Exposure Shader",
+            include_str!("shaders/exposureShader.wgsl"),
+            &ShaderDefines::new().set("HDR_OUTPUT", hdr_output)
+        )?;
+        let source_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Exposure Pipeline Layout"),
+            bind_group_layouts: &[&source_layout],
+            push_constant_ranges: &[],
+        });
+        let tonemap_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Tonemap Pipeline Layout"),
+            bind_group_layouts: &[&source_layout, &adapted_layout],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |label: &str, layout: &wgpu::PipelineLayout, entry_point: &str, format: wgpu::TextureFormat, blend: wgpu::BlendState| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None
+            })
+        };
+        let luminance_pipeline = create_pipeline(
+            "Luminance Pipeline", &source_pipeline_layout, "fs_luminance", LUMINANCE_FORMAT, wgpu::BlendState::REPLACE
+        );
+        let downsample_pipeline = create_pipeline(
+            "Luminance Downsample Pipeline", &source_pipeline_layout, "fs_downsample", LUMINANCE_FORMAT, wgpu::BlendState::REPLACE
+        );
+        // Moves the adapted value towards this frame's by the blend constant
+        let adapt_blend = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Constant,
+            dst_factor: wgpu::BlendFactor::OneMinusConstant,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let adapt_pipeline = create_pipeline(
+            "Exposure Adapt Pipeline", &source_pipeline_layout, "fs_adapt", LUMINANCE_FORMAT,
+            wgpu::BlendState { color: adapt_blend, alpha: adapt_blend }
+        );
+        let tonemap_pipeline = create_pipeline(
+            "Tonemap Pipeline", &tonemap_pipeline_layout, "fs_tonemap", format, wgpu::BlendState::REPLACE
+        );
+
+        Ok(Self {
+            luminance_pipeline,
+            downsample_pipeline,
+            adapt_pipeline,
+            tonemap_pipeline,
+            source_layout,
+            sampler,
+            scene_bind_group,
+            luminance_texture,
+            luminance_views,
+            luminance_bind_groups,
+            adapted_texture,
+            adapted_view,
+            adapted_bind_group,
+            settings_buffer,
+            adaptation: 1.0,
+            measured: false,
+        })
+    }
+
+    fn create_source_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        view: &wgpu::TextureView
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                }
+            ],
+            label: Some("Exposure Source Bind Group"),
+        })
+    }
+
+    /// Points the pass at a new scene texture after a resize.
+    pub fn resize(&mut self, device: &wgpu::Device, scene_texture: &Texture) {
+        self.scene_bind_group = Self::create_source_bind_group(device, &self.source_layout, &self.sampler, &scene_texture.view);
+    }
+
+    pub fn memory(&self) -> MemoryUsage {
+        MemoryUsage::texture(&self.luminance_texture)
+            + MemoryUsage::texture(&self.adapted_texture)
+            + MemoryUsage::buffer(&self.settings_buffer)
+    }
+
+    /// `min_exposure` replaces the setting's own, which status effects can raise.
+    pub fn prepare(&mut self, queue: &wgpu::Queue, delta_time: f32, min_exposure: f32, graphics: &GraphicsSettings) {
+        let settings = ExposureSettings {
+            key: KEY,
+            min_exposure,
+            max_exposure: graphics.max_exposure,
+            gamma: graphics.gamma,
+            paper_white: graphics.paper_white,
+            peak_brightness: graphics.peak_brightness,
+            _padding: [0.0; 2],
+        };
+        queue.write_buffer(&self.settings_buffer, 0, bytemuck::cast_slice(&[settings]));
+        self.adaptation = 1.0 - (-delta_time as f64 * ADAPTATION_SPEED as f64).exp();
+    }
+
+    /// Measures the scene's luminance and adapts exposure towards it. Must run
+    /// after the scene is complete and before `draw`.
+    pub fn measure(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let passes = std::iter::once((&self.luminance_pipeline, &self.scene_bind_group, &self.luminance_views[0]))
+            .chain(self.luminance_bind_groups.iter().zip(&self.luminance_views[1..])
+                .map(|(source, target)| (&self.downsample_pipeline, source, target)));
+        for (pipeline, source, target) in passes {
+            let mut render_pass = Self::begin_pass(encoder, "Luminance Pass", target, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, source, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        let mut render_pass = Self::begin_pass(encoder, "Exposure Adapt Pass", &self.adapted_view, wgpu::LoadOp::Load);
+        render_pass.set_pipeline(&self.adapt_pipeline);
+        render_pass.set_bind_group(0, self.luminance_bind_groups.last().expect("luminance has mips"), &[]);
+        // Start fully adapted to the first frame rather than fading in from black
+        let adaptation = if self.measured { self.adaptation } else { 1.0 };
+        render_pass.set_blend_constant(wgpu::Color { r: adaptation, g: adaptation, b: adaptation, a: adaptation });
+        render_pass.draw(0..3, 0..1);
+        drop(render_pass);
+        self.measured = true;
+    }
+
+    fn begin_pass<'e>(
+        encoder: &'e mut wgpu::CommandEncoder,
+        label: &str,
+        view: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>
+    ) -> wgpu::RenderPass<'e> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        })
+    }
+
+    /// Draws the scene to the screen with exposure and tonemapping applied.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_pipeline(&self.tonemap_pipeline);
+        render_pass.set_bind_group(0, &self.scene_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.adapted_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
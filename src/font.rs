@@ -0,0 +1,28 @@
+use font8x8::UnicodeFonts;
+
+/// Width and height of every glyph, in font pixels.
+pub const GLYPH_SIZE: u32 = 8;
+
+fn glyph(c: char) -> [u8; 8] {
+    font8x8::BASIC_FONTS.get(c)
+        .or_else(|| font8x8::BASIC_FONTS.get('?'))
+        .unwrap_or_default()
+}
+
+/// Calls `f(x, y)` for every lit font pixel in `text`, with (0, 0) at the
+/// top-left of the first character and y increasing downwards. Newlines
+/// start a new row of characters.
+pub fn for_each_pixel(text: &str, mut f: impl FnMut(u32, u32)) {
+    for (line_index, line) in text.lines().enumerate() {
+        for (char_index, c) in line.chars().enumerate() {
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..GLYPH_SIZE {
+                    // Bit 0 is the leftmost pixel
+                    if bits & (1 << column) != 0 {
+                        f(char_index as u32 * GLYPH_SIZE + column, line_index as u32 * GLYPH_SIZE + row as u32);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! The safe-mode renderer. Models are lit by the sun in a single pass
+//! straight onto the screen, with no G-buffer, water, reflections or
+//! post-processing, for drivers that can't cope with the full renderer.
+
+use crate::{draw_data::DrawData, model::{DrawModel, Model, ModelVertex, Vertex}, pipeline_cache::create_shader_module, texture::Texture};
+
+pub struct ForwardRenderer {
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ForwardRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_layout: &wgpu::BindGroupLayout,
+        sky_layout: &wgpu::BindGroupLayout,
+        draw_data: &DrawData
+    ) -> anyhow::Result<Self> {
+        let shader = create_shader_module(
+            device, "Forward Shader", include_str!("shaders/forwardShader.wgsl"), &draw_data.defines()
+        )?;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Forward Pipeline Layout"),
+            bind_group_layouts: &[Some(camera_layout), Some(sky_layout), draw_data.bind_group_layout()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>(),
+            push_constant_ranges: &draw_data.push_constant_ranges(),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Forward Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    ModelVertex::desc()
+                ],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None
+        });
+
+        Ok(Self { pipeline })
+    }
+
+    /// Draws `models` into a pass with a color and depth attachment. Model
+    /// `i` uses draw `i` of `draw_data`.
+    pub fn draw<'m>(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'m>,
+        camera_bind_group: &wgpu::BindGroup,
+        sky_bind_group: &wgpu::BindGroup,
+        draw_data: &DrawData,
+        models: impl IntoIterator<Item = &'m Model>
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, sky_bind_group, &[]);
+        for (i, model) in models.into_iter().enumerate() {
+            draw_data.bind(render_pass, 2, i);
+            render_pass.draw_model(model);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Light shafts. A screen-space radial blur of the visible sky towards the
+//! sun, added on top of the scene so light streams through gaps in geometry.
+
+use crate::render_cache::RenderCache;
+
+/// Samples taken along each pixel's path to the sun.
+const SAMPLES: u32 = 48;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GodraysUniform {
+    intensity: f32,
+    samples: u32,
+    /// How much each sample further from the pixel counts.
+    decay: f32,
+    /// Fraction of the way to the sun that's sampled.
+    reach: f32,
+}
+
+pub struct Godrays {
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Godrays {
+    /// `gbuf_layout`, `camera_layout` and `sky_layout` are bound at groups 0-2
+    /// when drawing; the effect's settings go in group 3.
+    pub fn new(
+        device: &wgpu::Device,
+        cache: &RenderCache,
+        format: wgpu::TextureFormat,
+        gbuf_layout: &wgpu::BindGroupLayout,
+        camera_layout: &wgpu::BindGroupLayout,
+        sky_layout: &wgpu::BindGroupLayout
+    ) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Godrays Uniform Buffer"),
+            size: std::mem::size_of::<GodraysUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = cache.uniform_layout(wgpu::ShaderStages::FRAGMENT);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("godrays_bind_group"),
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/godraysShader.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Godrays Pipeline Layout"),
+            bind_group_layouts: &[gbuf_layout, camera_layout, sky_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Godrays Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    // Light is added on top of whatever's already there
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::One,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None
+        });
+
+        Self { pipeline, uniform_buffer, bind_group }
+    }
+
+    /// `intensity` comes from the time of day; 0 turns the effect off.
+    pub fn prepare(&self, queue: &wgpu::Queue, intensity: f32) {
+        let uniform = GodraysUniform {
+            intensity,
+            samples: SAMPLES,
+            decay: 0.97,
+            reach: 0.6,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub fn draw(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        gbuf_bind_group: &wgpu::BindGroup,
+        camera_bind_group: &wgpu::BindGroup,
+        sky_bind_group: &wgpu::BindGroup
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, gbuf_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, sky_bind_group, &[]);
+        render_pass.set_bind_group(3, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use winit::window::Window;
+
+/// GPU-related launch options.
+#[derive(Clone, Debug, Default)]
+pub struct GpuOptions {
+    /// The adapter to use, as an index from `--list-gpus` or part of its name.
+    pub adapter: Option<String>,
+    /// Start with the minimal renderer. See `GraphicsSettings::safe`.
+    pub safe_mode: bool,
+    /// Draw to an extended-range surface when there is one.
+    pub hdr: bool,
+}
+
+/// Optional GPU functionality that some render paths depend on. Paths that
+/// need something missing here fall back to a compatible alternative.
+#[derive(Copy, Clone, Debug)]
+pub struct GpuCapabilities {
+    /// Small per-draw data can be pushed without a buffer. Not part of
+    /// WebGPU, so only native backends have it.
+    pub push_constants: bool,
+}
+
+impl GpuCapabilities {
+    pub fn of(adapter: &wgpu::Adapter) -> Self {
+        Self {
+            push_constants: adapter.features().contains(wgpu::Features::PUSH_CONSTANTS),
+        }
+    }
+}
+
+/// What the game ended up running on, logged at startup and shown on the
+/// menu's System page, so GPU bug reports say which driver and limits were
+/// involved.
+pub struct GpuReport {
+    info: wgpu::AdapterInfo,
+    capabilities: GpuCapabilities,
+    /// The features the device was created with.
+    features: wgpu::Features,
+    /// The limits the device was created with.
+    limits: wgpu::Limits,
+    surface_formats: Vec<wgpu::TextureFormat>,
+    present_modes: Vec<wgpu::PresentMode>,
+    surface_format: wgpu::TextureFormat,
+    present_mode: wgpu::PresentMode,
+}
+
+impl GpuReport {
+    pub fn new(
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        surface_caps: &wgpu::SurfaceCapabilities,
+        config: &wgpu::SurfaceConfiguration
+    ) -> Self {
+        Self {
+            info: adapter.get_info(),
+            capabilities: GpuCapabilities::of(adapter),
+            features: device.features(),
+            limits: device.limits(),
+            surface_formats: surface_caps.formats.clone(),
+            present_modes: surface_caps.present_modes.clone(),
+            surface_format: config.format,
+            present_mode: config.present_mode,
+        }
+    }
+
+    /// The report as short lines of text.
+    pub fn lines(&self) -> Vec<String> {
+        let yes_no = |yes: bool| if yes { "yes" } else { "no" };
+        let info = &self.info;
+        let limits = &self.limits;
+        let features = if self.features.is_empty() { "none".to_string() } else { self.features.to_string() };
+        vec![
+            format!("GPU: {}", info.name),
+            format!("Type: {:?}", info.device_type),
+            format!("Backend: {:?}", info.backend),
+            format!("Driver: {} {}", info.driver, info.driver_info),
+            format!("Vendor: {:#06x}, device: {:#06x}", info.vendor, info.device),
+            format!("Push constants: {}", yes_no(self.capabilities.push_constants)),
+            format!("Features: {}", features),
+            format!("Max texture size: {}", limits.max_texture_dimension_2d),
+            format!("Max bind groups: {}", limits.max_bind_groups),
+            format!("Max buffer size: {} MiB", limits.max_buffer_size / (1024 * 1024)),
+            format!("Max push constant size: {}", limits.max_push_constant_size),
+            format!("Surface format: {:?}", self.surface_format),
+            format!("Present mode: {:?}", self.present_mode),
+            format!("Supported formats: {:?}", self.surface_formats),
+            format!("Supported present modes: {:?}", self.present_modes),
+        ]
+    }
+
+    /// Logs every line, plus the full limits at debug level.
+    pub fn log(&self) {
+        let _span = tracing::info_span!("gpu").entered();
+        for line in self.lines() {
+            tracing::info!("{}", line);
+        }
+        tracing::debug!("Device limits: {:?}", self.limits);
+    }
+}
+
+pub fn create_instance() -> wgpu::Instance {
+    // Backends::all => Vulkan + Metal + DX12 + Browser WebGPU
+    wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        #[cfg(not(target_arch="wasm32"))]
+        backends: wgpu::Backends::PRIMARY,
+        #[cfg(target_arch="wasm32")]
+        backends: wgpu::Backends::GL,
+        ..Default::default()
+    })
+}
+
+/// Creates an instance and a surface for `window`. Some platforms only allow
+/// this on the main thread; everything after can happen elsewhere.
+pub fn create_surface(window: Arc<Window>) -> anyhow::Result<(wgpu::Instance, wgpu::Surface<'static>)> {
+    let instance = create_instance();
+    let surface = instance.create_surface(window)?;
+    Ok((instance, surface))
+}
+
+/// Prints every adapter with the index `--gpu` accepts.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list_adapters() {
+    let instance = create_instance();
+    let adapters = instance.enumerate_adapters(wgpu::Backends::all());
+    if adapters.is_empty() {
+        println!("No GPUs found");
+    }
+    for (i, adapter) in adapters.iter().enumerate() {
+        let info = adapter.get_info();
+        println!("{}: {} ({:?}, {:?}, driver: {} {})", i, info.name, info.backend, info.device_type, info.driver, info.driver_info);
+    }
+}
+
+/// Finds the adapter the user asked for, if it exists and can draw to `surface`.
+#[cfg(not(target_arch = "wasm32"))]
+fn find_adapter(instance: &wgpu::Instance, surface: &wgpu::Surface, wanted: &str) -> Option<wgpu::Adapter> {
+    let adapters = instance.enumerate_adapters(wgpu::Backends::all());
+    let wanted_lower = wanted.to_lowercase();
+    let adapter = match wanted.parse::<usize>() {
+        Ok(index) => adapters.into_iter().nth(index),
+        Err(_) => adapters.into_iter().find(|a| a.get_info().name.to_lowercase().contains(&wanted_lower)),
+    }?;
+
+    if !adapter.is_surface_supported(surface) {
+        tracing::warn!("GPU {} can't present to this window", adapter.get_info().name);
+        return None;
+    }
+    Some(adapter)
+}
+
+/// Picks an adapter: the one requested in `options` if possible, then a
+/// high-performance one, then any hardware adapter, then a software fallback.
+pub async fn request_adapter(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface<'_>,
+    options: &GpuOptions
+) -> anyhow::Result<wgpu::Adapter> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(wanted) = &options.adapter {
+        match find_adapter(instance, surface, wanted) {
+            Some(adapter) => return Ok(adapter),
+            None => tracing::warn!("No usable GPU matches {:?}; choosing one automatically", wanted),
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = options;
+
+    let attempts = [
+        (wgpu::PowerPreference::HighPerformance, false),
+        (wgpu::PowerPreference::None, false),
+        (wgpu::PowerPreference::None, true),
+    ];
+    for (power_preference, force_fallback_adapter) in attempts {
+        match instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference,
+            compatible_surface: Some(surface),
+            force_fallback_adapter
+        }).await {
+            Ok(adapter) => return Ok(adapter),
+            Err(e) => tracing::warn!(
+                "No adapter for {:?} (fallback: {}): {}", power_preference, force_fallback_adapter, e
+            ),
+        }
+    }
+    Err(anyhow!("No GPU adapter can present to this window"))
+}
+
+pub async fn request_device(adapter: &wgpu::Adapter) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+    // WebGL doesn't support all of wgpu's features, so if
+    // we're building for the web, we'll have to disable some.
+    let preferred_limits = if cfg!(target_arch = "wasm32") {
+        wgpu::Limits::downlevel_webgl2_defaults()
+    } else {
+        wgpu::Limits::default()
+    };
+    // Older GPUs may not reach the default limits; settle for the downlevel ones
+    let required_limits = if preferred_limits.check_limits(&adapter.limits()) {
+        preferred_limits
+    } else {
+        tracing::warn!("GPU doesn't support the default limits; using downlevel limits");
+        wgpu::Limits::downlevel_defaults()
+    }.using_resolution(adapter.limits());
+
+    // Optional features are only requested when the adapter has them; check
+    // GpuCapabilities before relying on one
+    let required_features = adapter.features() & wgpu::Features::PUSH_CONSTANTS; // Full list: https://docs.rs/wgpu/latest/wgpu/struct.Features.html
+    let required_limits = wgpu::Limits {
+        // 128 bytes is the most every backend with push constants guarantees
+        max_push_constant_size: if required_features.contains(wgpu::Features::PUSH_CONSTANTS) {
+            adapter.limits().max_push_constant_size.min(128)
+        } else {
+            0
+        },
+        ..required_limits
+    };
+
+    Ok(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            required_features,
+            required_limits,
+            label: None,
+            memory_hints: Default::default(),
+            trace: wgpu::Trace::Off,
+        },
+    ).await?)
+}
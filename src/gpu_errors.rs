@@ -0,0 +1,82 @@
+use std::{collections::VecDeque, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}};
+
+use crate::overlay::Overlay;
+
+/// Errors reported asynchronously by the GPU device. Instead of wgpu's
+/// default of panicking, errors are logged and kept for the validation
+/// overlay, and fatal ones flag the device for recreation.
+#[derive(Default)]
+pub struct GpuErrors {
+    messages: Mutex<VecDeque<String>>,
+    needs_recovery: AtomicBool,
+}
+
+impl GpuErrors {
+    /// How many recent errors are kept for the overlay.
+    const KEPT_MESSAGES: usize = 8;
+    const TEXT_SCALE: f32 = 1.0;
+
+    /// Installs error and device-lost handlers on `device`.
+    pub fn install(device: &wgpu::Device) -> Arc<Self> {
+        let errors = Arc::new(Self::default());
+
+        let handler_errors = errors.clone();
+        device.on_uncaptured_error(Box::new(move |error| {
+            if matches!(error, wgpu::Error::OutOfMemory { .. }) {
+                handler_errors.needs_recovery.store(true, Ordering::Relaxed);
+            }
+            tracing::error!("Uncaptured GPU error: {}", error);
+            handler_errors.push(error.to_string());
+        }));
+
+        let lost_errors = errors.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Destroyed is reported when we drop the device ourselves
+            if reason != wgpu::DeviceLostReason::Destroyed {
+                tracing::error!("GPU device lost ({:?}): {}", reason, message);
+                lost_errors.needs_recovery.store(true, Ordering::Relaxed);
+            }
+        });
+
+        errors
+    }
+
+    fn push(&self, message: String) {
+        let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+        if messages.len() == Self::KEPT_MESSAGES {
+            messages.pop_front();
+        }
+        messages.push_back(message);
+    }
+
+    /// Marks the device as needing recreation, e.g. after the surface reports it's out of memory.
+    pub fn request_recovery(&self) {
+        self.needs_recovery.store(true, Ordering::Relaxed);
+    }
+
+    pub fn needs_recovery(&self) -> bool {
+        self.needs_recovery.load(Ordering::Relaxed)
+    }
+
+    /// Shows recent errors along the bottom of the screen. Only drawn in
+    /// debug builds, where validation errors are almost always our bugs.
+    pub fn draw_overlay(&self, overlay: &mut Overlay, size: winit::dpi::PhysicalSize<u32>) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+        if messages.is_empty() {
+            return;
+        }
+
+        // Only show the first line of each error; the full text is in the log
+        let line_height = Overlay::line_height(Self::TEXT_SCALE) + 2.0;
+        let top = size.height as f32 - line_height * (messages.len() + 1) as f32 - 4.0;
+        overlay.rect(0.0, top, size.width as f32, size.height as f32 - top, [0.3, 0.0, 0.0, 0.8]);
+        overlay.text(4.0, top + 2.0, "GPU errors:", Self::TEXT_SCALE, [1.0, 1.0, 1.0, 1.0]);
+        for (i, message) in messages.iter().enumerate() {
+            let first_line = message.lines().next().unwrap_or_default();
+            overlay.text(4.0, top + 2.0 + line_height * (i + 1) as f32, first_line, Self::TEXT_SCALE, [1.0, 0.6, 0.6, 1.0]);
+        }
+    }
+}
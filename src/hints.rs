@@ -0,0 +1,101 @@
+//! Tutorial hints. Each appears when something makes it relevant and goes
+//! away once the player does what it suggests. Finished hints are kept in the
+//! player save, so they're only shown once.
+
+use std::collections::HashSet;
+
+use crate::{events::{EventBus, GameEvent, Subscription}, overlay::Overlay};
+
+struct Hint {
+    /// Stored in the save file, so it shouldn't change.
+    id: &'static str,
+    text: &'static str,
+    /// Whether an event makes this hint relevant.
+    shows_on: fn(&GameEvent) -> bool,
+    /// Whether an event means the player has done what the hint says.
+    done_on: fn(&GameEvent) -> bool,
+}
+
+const HINTS: &[Hint] = &[
+    Hint {
+        id: "move",
+        text: "Use WASD to move and the mouse to look around",
+        shows_on: |event| matches!(event, GameEvent::WorldLoaded),
+        done_on: |event| matches!(event, GameEvent::PlayerMoved { from, to } if from.x != to.x || from.z != to.z),
+    },
+    Hint {
+        id: "fly",
+        text: "Hold Space to fly up and Shift to fly down",
+        shows_on: |event| matches!(event, GameEvent::WorldLoaded),
+        done_on: |event| matches!(event, GameEvent::PlayerMoved { from, to } if from.y != to.y),
+    },
+    Hint {
+        id: "torch",
+        text: "It's getting dark. Press T to light your torch",
+        shows_on: |event| matches!(event, GameEvent::DaylightChanged { day: false }),
+        done_on: |event| matches!(event, GameEvent::TorchToggled { held: true }),
+    },
+];
+
+pub struct Hints {
+    completed: HashSet<String>,
+    /// Hints waiting to be completed, in the order they appeared. Only the
+    /// first is on screen.
+    active: Vec<&'static Hint>,
+    events: Subscription,
+}
+
+impl Hints {
+    const TEXT_SCALE: f32 = 2.0;
+
+    /// `completed` holds ids from the save file.
+    pub fn new(completed: &[String], events: &EventBus) -> Self {
+        Self {
+            completed: completed.iter().cloned().collect(),
+            active: Vec::new(),
+            events: events.subscribe(),
+        }
+    }
+
+    /// Ids of every completed hint, for the save file.
+    pub fn completed(&self) -> Vec<String> {
+        let mut ids: Vec<_> = self.completed.iter().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Returns whether a hint was completed, so the save can be written.
+    pub fn update(&mut self) -> bool {
+        let mut completed_any = false;
+        for event in self.events.drain() {
+            for hint in HINTS {
+                if (hint.done_on)(&event) && !self.completed.contains(hint.id) {
+                    self.completed.insert(hint.id.to_string());
+                    self.active.retain(|active| active.id != hint.id);
+                    completed_any = true;
+                } else if (hint.shows_on)(&event)
+                    && !self.completed.contains(hint.id)
+                    && !self.active.iter().any(|active| active.id == hint.id)
+                {
+                    self.active.push(hint);
+                }
+            }
+        }
+        completed_any
+    }
+
+    /// Shows the current hint centered near the bottom of the screen.
+    /// Nothing is drawn if hints are turned off.
+    pub fn draw(&self, overlay: &mut Overlay, size: winit::dpi::PhysicalSize<u32>, enabled: bool) {
+        let Some(hint) = self.active.first().filter(|_| enabled) else {
+            return;
+        };
+        // Glyphs are square, so a character is as wide as a line is high
+        let width = hint.text.len() as f32 * Overlay::line_height(Self::TEXT_SCALE);
+        let height = Overlay::line_height(Self::TEXT_SCALE);
+        let x = (size.width as f32 - width) / 2.0;
+        let y = size.height as f32 * 0.75;
+        overlay.rect(x - 10.0, y - 8.0, width + 20.0, height + 16.0, [0.0, 0.0, 0.0, 0.6]);
+        overlay.text(x, y, hint.text, Self::TEXT_SCALE, [1.0, 1.0, 1.0, 1.0]);
+    }
+}
@@ -0,0 +1,129 @@
+//! Dynamic point lights, lit in the deferred lighting pass. These are for
+//! lights that move, like a held torch; they're separate from any lighting
+//! baked into the world.
+
+use cgmath::{EuclideanSpace, Point3};
+
+use crate::{camera::Camera, memory::MemoryUsage, render_cache::RenderCache};
+
+/// Point lights the lighting pass can handle at once. Extra lights are dropped.
+pub const MAX_LIGHTS: usize = 8;
+
+#[derive(Copy, Clone, Debug)]
+pub struct PointLight {
+    pub position: Point3<f32>,
+    pub color: [f32; 3],
+    /// Distance where the light fades to nothing.
+    pub radius: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct PointLightRaw {
+    /// w is the radius
+    position: [f32; 4],
+    /// w is unused
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightsUniform {
+    count: u32,
+    _padding: [u32; 3],
+    lights: [PointLightRaw; MAX_LIGHTS],
+}
+
+pub struct Lights {
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    buffer: wgpu::Buffer,
+}
+
+impl Lights {
+    pub fn new(device: &wgpu::Device, cache: &RenderCache) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lights Buffer"),
+            size: std::mem::size_of::<LightsUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = cache.uniform_layout(wgpu::ShaderStages::FRAGMENT);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("lights_bind_group"),
+        });
+
+        Self { bind_group_layout, bind_group, buffer }
+    }
+
+    pub fn prepare(&self, queue: &wgpu::Queue, lights: &[PointLight]) {
+        if lights.len() > MAX_LIGHTS {
+            tracing::warn!("{} point lights, but only {} are drawn", lights.len(), MAX_LIGHTS);
+        }
+
+        let mut uniform = LightsUniform {
+            count: lights.len().min(MAX_LIGHTS) as u32,
+            _padding: [0; 3],
+            lights: [PointLightRaw::default(); MAX_LIGHTS],
+        };
+        for (raw, light) in uniform.lights.iter_mut().zip(lights) {
+            *raw = PointLightRaw {
+                position: light.position.to_vec().extend(light.radius).into(),
+                color: [light.color[0], light.color[1], light.color[2], 0.0],
+            };
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub fn memory(&self) -> MemoryUsage {
+        MemoryUsage::buffer(&self.buffer)
+    }
+}
+
+/// A torch held by the player, lighting their surroundings as they move.
+pub struct Torch {
+    pub held: bool,
+    time: f32,
+}
+
+impl Torch {
+    const COLOR: [f32; 3] = [1.0, 0.62, 0.3];
+    const RADIUS: f32 = 12.0;
+
+    pub fn new() -> Self {
+        Self { held: false, time: 0.0 }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        self.time += delta_time;
+    }
+
+    /// The torch's light, if it's held. It sits down and to the right of the
+    /// camera, where the torch would be in the player's hand.
+    pub fn light(&self, camera: &Camera) -> Option<PointLight> {
+        if !self.held {
+            return None;
+        }
+
+        // Overlapping sines at unrelated rates make a flicker that doesn't
+        // obviously repeat
+        let t = self.time;
+        let flicker = 0.9
+            + 0.05 * (t * 7.3).sin()
+            + 0.03 * (t * 13.1 + 1.7).sin()
+            + 0.02 * (t * 23.9 + 4.1).sin();
+
+        Some(PointLight {
+            position: camera.position() + camera.right() * 0.35 - camera.up() * 0.3 + camera.forward() * 0.4,
+            color: Self::COLOR.map(|c| c * flicker * 2.0),
+            radius: Self::RADIUS * (0.95 + 0.05 * flicker),
+        })
+    }
+}
@@ -0,0 +1,63 @@
+use crate::overlay::Overlay;
+
+/// A step of startup, in the order they happen.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoadingStage {
+    GpuInit,
+    Assets,
+}
+
+impl LoadingStage {
+    const COUNT: usize = 2;
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LoadingStage::GpuInit => "Initializing GPU",
+            LoadingStage::Assets => "Loading assets",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// The screen shown while startup work runs in the background.
+pub struct LoadingScreen {
+    stage: LoadingStage,
+    started: std::time::Instant,
+}
+
+impl LoadingScreen {
+    const TEXT_SCALE: f32 = 3.0;
+    const BAR_WIDTH: f32 = 400.0;
+    const BAR_HEIGHT: f32 = 12.0;
+
+    pub fn new(stage: LoadingStage) -> Self {
+        Self { stage, started: std::time::Instant::now() }
+    }
+
+    pub fn set_stage(&mut self, stage: LoadingStage) {
+        tracing::info!("Startup stage: {}", stage.label());
+        self.stage = stage;
+    }
+
+    pub fn draw(&self, overlay: &mut Overlay, size: winit::dpi::PhysicalSize<u32>) {
+        let (width, height) = (size.width as f32, size.height as f32);
+        overlay.rect(0.0, 0.0, width, height, [0.05, 0.05, 0.08, 1.0]);
+
+        // Animate trailing dots so it's obvious we haven't frozen
+        let dots = (self.started.elapsed().as_millis() / 400 % 4) as usize;
+        let label = format!("{}{}", self.stage.label(), ".".repeat(dots));
+        let line_height = Overlay::line_height(Self::TEXT_SCALE);
+        overlay.text(
+            (width - Self::BAR_WIDTH) / 2.0, height / 2.0 - line_height * 2.0,
+            &label, Self::TEXT_SCALE, [1.0, 1.0, 1.0, 1.0]
+        );
+
+        let bar_x = (width - Self::BAR_WIDTH) / 2.0;
+        let progress = (self.stage.index() + 1) as f32 / (LoadingStage::COUNT + 1) as f32;
+        overlay.rect(bar_x, height / 2.0, Self::BAR_WIDTH, Self::BAR_HEIGHT, [0.2, 0.2, 0.25, 1.0]);
+        overlay.rect(bar_x, height / 2.0, Self::BAR_WIDTH * progress, Self::BAR_HEIGHT, [0.4, 0.7, 1.0, 1.0]);
+    }
+}
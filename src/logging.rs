@@ -0,0 +1,160 @@
+use std::{collections::VecDeque, fmt::Write, sync::{Mutex, TryLockError}};
+
+use tracing::{field::{Field, Visit}, Level};
+use tracing_subscriber::{filter::Targets, layer::{Context, SubscriberExt}, registry::LookupSpan, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::overlay::Overlay;
+
+/// How many log lines are kept in memory for the in-game viewer.
+const CAPTURED_LINES: usize = 200;
+
+#[derive(Clone, Debug)]
+pub struct LogLine {
+    pub level: Level,
+    /// The names of the spans the event happened in, outermost first, joined by ':'.
+    pub spans: String,
+    pub message: String,
+}
+
+static CAPTURED: Mutex<VecDeque<LogLine>> = Mutex::new(VecDeque::new());
+
+fn captured() -> std::sync::MutexGuard<'static, VecDeque<LogLine>> {
+    CAPTURED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Installs the global subscriber: formatted output to stdout filtered by
+/// `RUST_LOG`, plus an in-memory capture of this crate's events for the
+/// in-game log viewer. Records from the `log` crate (used by wgpu) are
+/// forwarded too.
+pub fn init() {
+    let stdout_filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("warn,VoxelGame=info"));
+    let capture_filter = Targets::new()
+        .with_target("VoxelGame", Level::TRACE)
+        .with_default(Level::WARN);
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(stdout_filter))
+        .with(CaptureLayer.with_filter(capture_filter))
+        .init();
+}
+
+/// Returns up to `count` of the most recent captured lines at `max_level` or
+/// more severe, oldest first.
+pub fn recent_lines(max_level: Level, count: usize) -> Vec<LogLine> {
+    filter_lines(&captured(), max_level, count)
+}
+
+/// Like `recent_lines`, but returns `None` instead of waiting if the lines
+/// are locked. For the panic hook, where the panicking thread may be the one
+/// holding the lock.
+pub fn try_recent_lines(max_level: Level, count: usize) -> Option<Vec<LogLine>> {
+    let captured = match CAPTURED.try_lock() {
+        Ok(captured) => captured,
+        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(TryLockError::WouldBlock) => return None,
+    };
+    Some(filter_lines(&captured, max_level, count))
+}
+
+fn filter_lines(captured: &VecDeque<LogLine>, max_level: Level, count: usize) -> Vec<LogLine> {
+    let mut lines: Vec<LogLine> = captured.iter().rev()
+        .filter(|line| line.level <= max_level)
+        .take(count)
+        .cloned()
+        .collect();
+    lines.reverse();
+    lines
+}
+
+struct CaptureLayer;
+
+impl<S> Layer<S> for CaptureLayer where S: tracing::Subscriber + for<'a> LookupSpan<'a> {
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let spans = ctx.event_scope(event)
+            .map(|scope| scope.from_root().map(|span| span.name()).collect::<Vec<_>>().join(":"))
+            .unwrap_or_default();
+
+        let mut captured = captured();
+        if captured.len() == CAPTURED_LINES {
+            captured.pop_front();
+        }
+        captured.push_back(LogLine {
+            level: *event.metadata().level(),
+            spans,
+            message: visitor.message,
+        });
+    }
+}
+
+/// Formats an event as its message followed by `key=value` for every other field.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.message.is_empty() {
+            self.message.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.message, "{}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// An in-game panel listing recent log lines, so problems can be diagnosed
+/// on machines without a console.
+pub struct LogViewer {
+    pub open: bool,
+    /// The most verbose level shown.
+    pub max_level: Level,
+}
+
+impl LogViewer {
+    const VISIBLE_LINES: usize = 24;
+    const TEXT_SCALE: f32 = 2.0;
+
+    pub fn new() -> Self {
+        Self { open: false, max_level: Level::INFO }
+    }
+
+    fn level_color(level: Level) -> [f32; 4] {
+        match level {
+            Level::ERROR => [1.0, 0.3, 0.3, 1.0],
+            Level::WARN => [1.0, 0.8, 0.2, 1.0],
+            Level::INFO => [0.9, 0.9, 0.9, 1.0],
+            Level::DEBUG => [0.5, 0.8, 1.0, 1.0],
+            Level::TRACE => [0.6, 0.6, 0.6, 1.0],
+        }
+    }
+
+    pub fn draw(&self, overlay: &mut Overlay, screen_width: f32) {
+        if !self.open {
+            return;
+        }
+
+        let line_height = Overlay::line_height(Self::TEXT_SCALE) + 2.0;
+        let lines = recent_lines(self.max_level, Self::VISIBLE_LINES);
+        overlay.rect(0.0, 0.0, screen_width, line_height * (Self::VISIBLE_LINES + 1) as f32 + 8.0, [0.0, 0.0, 0.0, 0.7]);
+        overlay.text(
+            4.0, 4.0,
+            &format!("Log viewer - showing {} and above (keys 1-5 to change)", self.max_level),
+            Self::TEXT_SCALE, [1.0, 1.0, 1.0, 1.0]
+        );
+        for (i, line) in lines.iter().enumerate() {
+            let text = if line.spans.is_empty() {
+                format!("{:5} {}", line.level, line.message)
+            } else {
+                format!("{:5} [{}] {}", line.level, line.spans, line.message)
+            };
+            overlay.text(4.0, 4.0 + line_height * (i + 1) as f32, &text, Self::TEXT_SCALE, Self::level_color(line.level));
+        }
+    }
+}
@@ -1,16 +1,81 @@
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
 
+use clap::Parser;
 use wgpu::util::DeviceExt;
 use winit::{
-    application::ApplicationHandler, event::{ElementState, KeyEvent, WindowEvent}, event_loop::{ActiveEventLoop, ControlFlow, EventLoop}, keyboard::{KeyCode, PhysicalKey}, window::{CursorGrabMode, Window, WindowId}
+    application::ApplicationHandler, event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, StartCause, WindowEvent}, event_loop::{ActiveEventLoop, ControlFlow, EventLoop}, keyboard::{KeyCode, PhysicalKey}, window::{CursorGrabMode, Window, WindowId}
 };
 
-use crate::{camera::{Camera, CameraController, CameraUniform}, model::{DrawModel, Model, Vertex}, texture::Texture};
+use crate::{achievements::Achievements, auto_quality::AutoQuality, camera::{Camera, CameraController, CameraUniform}, collision::{Aabb, CollisionLog, CollisionResult}, debug_draw::DebugDraw, draw_data::DrawData, events::{EventBus, GameEvent}, exposure::Exposure, forward::ForwardRenderer, gpu::{GpuCapabilities, GpuOptions, GpuReport}, godrays::Godrays, gpu_errors::GpuErrors, hints::Hints, lights::{Lights, Torch}, loading::{LoadingScreen, LoadingStage}, logging::LogViewer, memory::{MemoryStats, MemoryUsage}, menu::{MainMenu, MenuAction, MenuSettings}, model::{DrawModel, MeshData, Model, Vertex}, overlay::Overlay, picking::ObjectPicker, pipeline_cache::PipelineCache, planar::PlanarReflection, render_cache::RenderCache, render_scale::RenderScale, save::PlayerSave, settings::{AmbientOcclusion, GameplaySettings, GraphicsSettings, ReflectionMode}, shader_preprocessor::ShaderDefines, sky::{SkyGradient, SkyUniform, TimeOfDay}, ssr::ScreenSpaceReflections, stats::StatsTracker, status_effects::{StatusEffect, StatusEffects}, texture::Texture, water::Water};
 
+mod achievements;
+mod auto_quality;
 mod camera;
+mod cli;
+mod collision;
+mod crash;
+mod debug_draw;
+mod draw_data;
+mod events;
+mod exposure;
+mod font;
+mod forward;
+mod godrays;
+mod gpu;
+mod gpu_errors;
+mod hints;
+mod lights;
+mod loading;
+mod logging;
+mod memory;
+mod menu;
+mod overlay;
+mod picking;
+mod pipeline_cache;
+mod planar;
+mod render_cache;
+mod render_scale;
+#[cfg(all(test, feature = "gpu-tests"))]
+mod render_tests;
+mod settings;
+mod shader_preprocessor;
 mod texture;
 mod model;
 mod resources;
+mod save;
+mod session;
+mod sky;
+mod stats;
+mod status_effects;
+mod ssr;
+mod ui;
+mod water;
+
+/// Half the width of the player's collision box.
+const PLAYER_HALF_WIDTH: f32 = 0.3;
+/// Height of the player's collision box.
+const PLAYER_HEIGHT: f32 = 1.8;
+/// Distance from the bottom of the player's collision box to the camera.
+const PLAYER_EYE_HEIGHT: f32 = 1.62;
+
+/// Distance from the camera where fog starts to fade terrain into the sky.
+const FOG_START: f32 = 50.0;
+/// Distance from the camera where fog completely hides terrain. This should
+/// be less than the camera's far plane.
+const FOG_END: f32 = 95.0;
+/// Real seconds per in-game day.
+const DAY_LENGTH: f32 = 20.0 * 60.0;
+/// Height of the water's surface, just above the bottom of the teapot.
+const WATER_LEVEL: f32 = -7.4;
+/// How far away the player can target things under the crosshair.
+const REACH: f32 = 8.0;
+/// Seconds between saves of progress like stats, in case the game crashes.
+const SAVE_INTERVAL: f32 = 30.0;
+/// Seconds the F8 debug key applies every status effect for.
+const DEBUG_EFFECT_DURATION: f32 = 30.0;
+/// Time between frames while the window is unfocused.
+#[cfg(not(target_arch = "wasm32"))]
+const UNFOCUSED_FRAME_TIME: std::time::Duration = std::time::Duration::from_millis(100);
 
 struct State<'a> {
     surface: wgpu::Surface<'a>,
@@ -19,13 +84,41 @@ struct State<'a> {
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
     window: Arc<Window>,
+    gpu_options: GpuOptions,
+    gpu_errors: Arc<GpuErrors>,
+    gpu_report: GpuReport,
 
     gbuf_render_pipeline: wgpu::RenderPipeline,
+    draw_data: DrawData,
     depth_texture: Texture,
     normal_texture: Texture,
     color_texture: Texture,
+    gbuf_bind_group_layout: wgpu::BindGroupLayout,
     gbuf_bind_group: wgpu::BindGroup,
-    lighting_render_pipeline: wgpu::RenderPipeline,
+    /// Variants of the lighting pass for each ambient occlusion setting.
+    lighting_pipelines: PipelineCache,
+    scene_texture: Texture,
+    /// The scene after reflections and other effects, before exposure.
+    post_texture: Texture,
+    render_scale: RenderScale,
+    auto_quality: AutoQuality,
+    /// Seconds the last frame spent updating and rendering, not counting
+    /// waits on the swapchain. Auto quality judges spare time from this.
+    work_time: f32,
+    /// How long the current frame has waited on the swapchain.
+    swapchain_wait: std::time::Duration,
+    /// What's under the crosshair, from the G-buffer's object IDs.
+    picker: ObjectPicker,
+    ssr: ScreenSpaceReflections,
+    godrays: Godrays,
+    exposure: Exposure,
+    /// Used instead of everything above in safe mode.
+    forward: ForwardRenderer,
+    planar_reflection: PlanarReflection,
+    graphics: GraphicsSettings,
+    gameplay: GameplaySettings,
+    debug_draw: DebugDraw,
+    overlay: Overlay,
 
     camera: Camera,
     camera_uniform: CameraUniform,
@@ -33,56 +126,101 @@ struct State<'a> {
     camera_bind_group: wgpu::BindGroup,
     camera_controller: CameraController,
 
-    model: Model
+    time_of_day: TimeOfDay,
+    sky_gradient: SkyGradient,
+    sky_buffer: wgpu::Buffer,
+    sky_bind_group: wgpu::BindGroup,
+    lights: Lights,
+    torch: Torch,
+
+    model: Option<Model>,
+    water: Water,
+    loading: Option<LoadingScreen>,
+
+    events: EventBus,
+    player_save: PlayerSave,
+    /// Seconds until progress is saved again.
+    save_timer: f32,
+    achievements: Achievements,
+    stats: StatsTracker,
+    hints: Hints,
+    effects: StatusEffects,
+    collision_log: CollisionLog,
+    show_collision_debug: bool,
+    memory: MemoryStats,
+    show_memory_stats: bool,
+    /// The window is unfocused, so the world is stopped.
+    paused: bool,
+    menu: Option<MainMenu>,
+    log_viewer: LogViewer
 }
 
-impl<'a> State<'a> {
-    async fn new(window: Arc<Window>) -> State<'a> {
-        // Backends::all => Vulkan + Metal + DX12 + Browser WebGPU
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            #[cfg(not(target_arch="wasm32"))]
-            backends: wgpu::Backends::PRIMARY,
-            #[cfg(target_arch="wasm32")]
-            backends: wgpu::Backends::GL,
-            ..Default::default()
-        });
+/// CPU-side state that survives recreating the GPU device.
+struct PersistentState {
+    camera: Camera,
+    camera_controller: CameraController,
+    time_of_day: TimeOfDay,
+    torch: Torch,
+    mesh: Option<MeshData>,
+    graphics: GraphicsSettings,
+    gameplay: GameplaySettings,
+    events: EventBus,
+    player_save: PlayerSave,
+    achievements: Achievements,
+    stats: StatsTracker,
+    hints: Hints,
+    effects: StatusEffects,
+    show_collision_debug: bool,
+    show_memory_stats: bool,
+    /// The window is unfocused, so the world is stopped.
+    paused: bool,
+    menu: Option<MainMenu>,
+    log_viewer: LogViewer,
+}
 
-        let surface = instance.create_surface(window.clone()).expect("Failed to create surface!");
+impl<'a> State<'a> {
+    /// Creates the device and every GPU resource. Without a mesh, the loading
+    /// screen is shown until `finish_loading` is called.
+    async fn new(
+        window: Arc<Window>,
+        (instance, surface): (wgpu::Instance, wgpu::Surface<'a>),
+        gpu_options: GpuOptions,
+        mesh: Option<MeshData>
+    ) -> anyhow::Result<State<'a>> {
+        let adapter = gpu::request_adapter(&instance, &surface, &gpu_options).await?;
+        let info = adapter.get_info();
+        let capabilities = GpuCapabilities::of(&adapter);
+        tracing::info!("Using GPU {} ({:?}) with {:?}", info.name, info.backend, capabilities);
+        crash::set_adapter(info.clone());
 
-        let adapter = instance.request_adapter(
-            &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false
-            },
-        ).await.expect("Failed to request adapter!");
-
-        let (device, queue) = adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::empty(), // Full list: https://docs.rs/wgpu/latest/wgpu/struct.Features.html
-                // WebGL doesn't support all of wgpu's features, so if
-                // we're building for the web, we'll have to disable some.
-                required_limits: if cfg!(target_arch = "wasm32") {
-                    wgpu::Limits::downlevel_webgl2_defaults()
-                } else {
-                    wgpu::Limits::default()
-                },
-                label: None,
-                memory_hints: Default::default(),
-                trace: wgpu::Trace::Off,
-            },
-        ).await.expect("Failed to get device!");
+        let (device, queue) = gpu::request_device(&adapter).await?;
+        let gpu_errors = GpuErrors::install(&device);
+        let cache = RenderCache::new(&device);
 
         let size = window.inner_size();
 
         let surface_caps = surface.get_capabilities(&adapter);
-        // Shader code in this tutorial assumes an sRGB surface texture. Using a different
-        // one will result in all the colors coming out darker. If you want to support non
-        // sRGB surfaces, you'll need to account for that when drawing to the frame.
+        // Shading happens in linear space and relies on the surface encoding
+        // to sRGB when written. Prefer an sRGB format, then one that can be
+        // viewed as sRGB, and only then anything else.
         let surface_format = surface_caps.formats.iter()
             .find(|f| f.is_srgb())
+            .or_else(|| surface_caps.formats.iter().find(|f| f.add_srgb_suffix().is_srgb()))
+            .or_else(|| surface_caps.formats.first())
             .copied()
-            .unwrap_or(surface_caps.formats[0]);
+            .ok_or_else(|| anyhow::anyhow!("The surface doesn't support any formats"))?;
+        // HDR output uses a float surface, which every backend treats as scRGB
+        let hdr_format = wgpu::TextureFormat::Rgba16Float;
+        let hdr_output = gpu_options.hdr && surface_caps.formats.contains(&hdr_format);
+        if gpu_options.hdr && !hdr_output {
+            tracing::warn!("HDR output was requested, but the display doesn't support it; using SDR");
+        }
+        let surface_format = if hdr_output { hdr_format } else { surface_format };
+        if !hdr_output && !surface_format.add_srgb_suffix().is_srgb() {
+            tracing::warn!("The surface has no sRGB format; colors will look too dark");
+        }
+        // Everything drawn to the screen goes through an sRGB view of the surface
+        let render_format = surface_format.add_srgb_suffix();
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
@@ -90,10 +228,11 @@ impl<'a> State<'a> {
             height: size.height,
             present_mode: wgpu::PresentMode::AutoVsync,
             alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
+            view_formats: if render_format == surface_format { vec![] } else { vec![render_format] },
             desired_maximum_frame_latency: 2,
         };
-        
+        let gpu_report = GpuReport::new(&adapter, &device, &surface_caps, &config);
+        gpu_report.log();
 
         let camera = Camera::new(
             config.width as f32 / config.height as f32,
@@ -111,21 +250,7 @@ impl<'a> State<'a> {
             }
         );
 
-        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }
-            ],
-            label: Some("camera_bind_group_layout"),
-        });
+        let camera_bind_group_layout = cache.uniform_layout(wgpu::ShaderStages::VERTEX_FRAGMENT);
 
         let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &camera_bind_group_layout,
@@ -138,20 +263,172 @@ impl<'a> State<'a> {
             label: Some("camera_bind_group"),
         });
 
-        let depth_texture = texture::Texture::create_gbuf_texture(&device, &config, "depth_texture", true);
-        let normal_texture = texture::Texture::create_gbuf_texture(&device, &config, "normal_texture", false);
-        let color_texture = texture::Texture::create_gbuf_texture(&device, &config, "color_texture", false);
+        let graphics = if gpu_options.safe_mode { GraphicsSettings::safe() } else { GraphicsSettings::default() };
+        // Everything up to tonemapping is drawn at the render scale
+        let scene_config = RenderScale::scaled_config(&device, &config, graphics.render_scale);
+        let depth_texture = texture::Texture::create_gbuf_texture(&device, &scene_config, "depth_texture", true);
+        let normal_texture = texture::Texture::create_gbuf_texture(&device, &scene_config, "normal_texture", false);
+        let color_texture = texture::Texture::create_gbuf_texture(&device, &scene_config, "color_texture", false);
+        let scene_texture = texture::Texture::create_render_target(&device, &scene_config, "scene_texture", Texture::HDR_FORMAT);
+        let post_texture = texture::Texture::create_render_target(&device, &scene_config, "post_texture", Texture::HDR_FORMAT);
         
-        let g_buffer_shader = device.create_shader_module(wgpu::include_wgsl!("shaders/gBufferShader.wgsl"));
+        let draw_data = DrawData::new(&device, capabilities.push_constants);
+        let gbuf_render_pipeline = Self::create_gbuf_pipeline(&device, &camera_bind_group_layout, &draw_data)?;
+
+        let gbuf_bind_group_layout = Self::create_gbuf_bind_group_layout(&device);
+        let gbuf_bind_group = Self::create_gbuf_bind_group(&device, &gbuf_bind_group_layout, &normal_texture, &color_texture, &depth_texture);
+
+        let time_of_day = TimeOfDay::new(0.3, DAY_LENGTH);
+        let sky_gradient = SkyGradient::load("sky.ron").await?;
+        let events = EventBus::default();
+        let player_save = PlayerSave::load();
+        let achievements = Achievements::load("achievements.ron", &player_save.achievements, &events).await?;
+        let stats = StatsTracker::new(player_save.stats.clone(), &events);
+        let hints = Hints::new(&player_save.completed_hints, &events);
+        let effects = StatusEffects::new(player_save.status_effects.clone());
+        let sky_uniform = SkyUniform::new(&time_of_day, &sky_gradient.sample(time_of_day.time), FOG_START, FOG_END);
+        let sky_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Sky Buffer"),
+                contents: bytemuck::cast_slice(&[sky_uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+        let sky_bind_group_layout = cache.uniform_layout(wgpu::ShaderStages::FRAGMENT);
+        let sky_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &sky_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sky_buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("sky_bind_group"),
+        });
+
+        let lights = Lights::new(&device, &cache);
+
+        let lighting_pipelines = Self::create_lighting_pipelines(
+            &device,
+            &gbuf_bind_group_layout,
+            &camera_bind_group_layout,
+            &sky_bind_group_layout,
+            &lights.bind_group_layout,
+            graphics.ambient_occlusion
+        )?;
+
+        let planar_reflection = PlanarReflection::new(&device, &cache, &scene_config, &sky_bind_group_layout, &draw_data)?;
+        let ssr = ScreenSpaceReflections::new(
+            &device, Texture::HDR_FORMAT, &gbuf_bind_group_layout, &camera_bind_group_layout, &sky_bind_group_layout, &scene_texture
+        );
+        let godrays = Godrays::new(
+            &device, &cache, Texture::HDR_FORMAT, &gbuf_bind_group_layout, &camera_bind_group_layout, &sky_bind_group_layout
+        );
+        let exposure = Exposure::new(&device, &cache, render_format, hdr_output, &post_texture)?;
+        let render_scale = RenderScale::new(&device, &cache, &config, graphics.render_scale, &depth_texture);
+        let picker = ObjectPicker::new(&device);
+        let forward = ForwardRenderer::new(&device, render_format, &camera_bind_group_layout, &sky_bind_group_layout, &draw_data)?;
+        let water = Water::new(
+            &device, &cache, &scene_config, WATER_LEVEL, &gbuf_bind_group_layout, &camera_bind_group_layout, &sky_bind_group_layout,
+            &planar_reflection.texture
+        )?;
+
+        let debug_draw = DebugDraw::new(&device, render_format, &camera_bind_group_layout);
+        let overlay = Overlay::new(&device, render_format);
+
+        let model = mesh.map(|mesh| Model::new(&device, mesh));
+        let loading = model.is_none().then(|| LoadingScreen::new(LoadingStage::GpuInit));
+        let menu = MainMenu::new(gpu_report.lines());
+
+        Ok(State {
+            surface,
+            window,
+            gpu_options,
+            gpu_errors,
+            gpu_report,
+            device,
+            queue,
+            size,
+            config,
+
+            gbuf_render_pipeline,
+            draw_data,
+            depth_texture,
+            normal_texture,
+            color_texture,
+            gbuf_bind_group_layout,
+            gbuf_bind_group,
+            lighting_pipelines,
+            scene_texture,
+            post_texture,
+            render_scale,
+            auto_quality: AutoQuality::new(&graphics),
+            work_time: 0.0,
+            swapchain_wait: std::time::Duration::ZERO,
+            picker,
+            ssr,
+            godrays,
+            exposure,
+            forward,
+            planar_reflection,
+            graphics,
+            gameplay: GameplaySettings::default(),
+            debug_draw,
+            overlay,
+
+            camera,
+            camera_uniform,
+            camera_buffer,
+            camera_bind_group,
+            camera_controller: CameraController::new(5.),
+
+            time_of_day,
+            sky_gradient,
+            sky_buffer,
+            sky_bind_group,
+            lights,
+            torch: Torch::new(),
+
+            model,
+            water,
+            loading,
+
+            events,
+            player_save,
+            save_timer: SAVE_INTERVAL,
+            achievements,
+            stats,
+            hints,
+            effects,
+            collision_log: CollisionLog::default(),
+            show_collision_debug: false,
+            memory: MemoryStats::default(),
+            show_memory_stats: false,
+            paused: false,
+            menu: Some(menu),
+            log_viewer: LogViewer::new()
+        })
+    }
+
+    /// Writes normals, colors and depth for every model into the G-buffer.
+    fn create_gbuf_pipeline(
+        device: &wgpu::Device,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        draw_data: &DrawData
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        let g_buffer_shader = pipeline_cache::create_shader_module(
+            device, "G-Buffer Shader", include_str!("shaders/gBufferShader.wgsl"), &draw_data.defines()
+        )?;
         let gbuf_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("G-Buffer Render Pipeline Layout"),
-            bind_group_layouts: &[
-                &camera_bind_group_layout
-            ],
-            push_constant_ranges: &[],
+            bind_group_layouts: &[Some(camera_bind_group_layout), draw_data.bind_group_layout()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>(),
+            push_constant_ranges: &draw_data.push_constant_ranges(),
         });
 
-        let gbuf_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        Ok(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("G-Buffer Render Pipeline"),
             layout: Some(&gbuf_pipeline_layout),
             vertex: wgpu::VertexState {
@@ -165,11 +442,20 @@ impl<'a> State<'a> {
             fragment: Some(wgpu::FragmentState {
                 module: &g_buffer_shader,
                 entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
+                targets: &[
+                    // 0: normal
+                    Some(wgpu::ColorTargetState {
+                        format: texture::Texture::GBUF_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    // 1: color
+                    Some(wgpu::ColorTargetState {
+                        format: texture::Texture::GBUF_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })
+                ],
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             }),
             primitive: wgpu::PrimitiveState {
@@ -198,22 +484,29 @@ impl<'a> State<'a> {
             },
             multiview: None,
             cache: None
-        });
+        }))
+    }
 
-        let gbuf_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+    /// Lets later passes read the G-buffer; see `create_gbuf_bind_group`.
+    fn create_gbuf_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("G-Buffer Bind Group Layout"),
             entries: &[
-                // 0: normal sampler
+                // 0: normal texture
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
                     count: None,
                 },
-                // 1: normal texture
+                // 1: color texture
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
-                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Texture {
                         multisampled: false,
                         view_dimension: wgpu::TextureViewDimension::D2,
@@ -221,18 +514,11 @@ impl<'a> State<'a> {
                     },
                     count: None,
                 },
-
-                // 2: color sampler
+                // 2: depth texture, read as a plain float texture since
+                // GL can't load from depth textures in shaders
                 wgpu::BindGroupLayoutEntry {
                     binding: 2,
-                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
-                    count: None,
-                },
-                // 3: color texture
-                wgpu::BindGroupLayoutEntry {
-                    binding: 3,
-                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Texture {
                         multisampled: false,
                         view_dimension: wgpu::TextureViewDimension::D2,
@@ -241,115 +527,175 @@ impl<'a> State<'a> {
                     count: None,
                 }
             ]
+        })
+    }
+
+    /// Lights the G-buffer into the HDR scene texture, with a variant for
+    /// each ambient occlusion mode.
+    fn create_lighting_pipelines(
+        device: &wgpu::Device,
+        gbuf_bind_group_layout: &wgpu::BindGroupLayout,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        sky_bind_group_layout: &wgpu::BindGroupLayout,
+        lights_bind_group_layout: &wgpu::BindGroupLayout,
+        ambient_occlusion: AmbientOcclusion
+    ) -> anyhow::Result<PipelineCache> {
+        let lighting_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Lighting Pipeline Layout"),
+            bind_group_layouts: &[
+                gbuf_bind_group_layout,
+                camera_bind_group_layout,
+                sky_bind_group_layout,
+                lights_bind_group_layout
+            ],
+            push_constant_ranges: &[],
         });
-        let gbuf_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &gbuf_bind_group_layout,
+        PipelineCache::new(
+            device,
+            "Lighting Shader",
+            include_str!("shaders/lightingShader.wgsl"),
+            Self::lighting_defines(ambient_occlusion),
+            Arc::new(move |device, lighting_shader| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Lighting Pipeline"),
+                layout: Some(&lighting_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: lighting_shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: lighting_shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: Texture::HDR_FORMAT,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    // Requires Features::DEPTH_CLIP_CONTROL
+                    unclipped_depth: false,
+                    // Requires Features::CONSERVATIVE_RASTERIZATION
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None
+            }))
+        )
+    }
+
+    fn create_gbuf_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        normal_texture: &Texture,
+        color_texture: &Texture,
+        depth_texture: &Texture
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
+                    resource: wgpu::BindingResource::TextureView(&normal_texture.view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&normal_texture.view),
+                    resource: wgpu::BindingResource::TextureView(&color_texture.view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: wgpu::BindingResource::Sampler(&color_texture.sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: wgpu::BindingResource::TextureView(&color_texture.view),
+                    resource: wgpu::BindingResource::TextureView(&depth_texture.view),
                 }
             ],
             label: Some("G-Buffer Bind Group"),
-        });
-
-        let lighting_shader = device.create_shader_module(wgpu::include_wgsl!("shaders/lightingShader.wgsl"));
-        let lighting_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Lighting Pipeline Layout"),
-            bind_group_layouts: &[
-                &gbuf_bind_group_layout
-            ],
-            push_constant_ranges: &[],
-        });
-        let lighting_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Lighting Pipeline"),
-            layout: Some(&lighting_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &lighting_shader,
-                entry_point: Some("vs_main"),
-                buffers: &[
-                    model::ModelVertex::desc()
-                ],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &lighting_shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    // TODO
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None
-        });
-
-        let model = Model::load("teapot.obj", &device).await.expect("Failed to load model");
+        })
+    }
 
-        State {
-            surface,
-            window,
-            device,
-            queue,
-            size,
-            config,
+    fn into_persistent(self) -> PersistentState {
+        PersistentState {
+            camera: self.camera,
+            camera_controller: self.camera_controller,
+            time_of_day: self.time_of_day,
+            torch: self.torch,
+            mesh: self.model.map(|model| model.mesh),
+            graphics: self.graphics,
+            gameplay: self.gameplay,
+            events: self.events,
+            player_save: self.player_save,
+            achievements: self.achievements,
+            stats: self.stats,
+            hints: self.hints,
+            effects: self.effects,
+            show_collision_debug: self.show_collision_debug,
+            show_memory_stats: self.show_memory_stats,
+            paused: self.paused,
+            menu: self.menu,
+            log_viewer: self.log_viewer,
+        }
+    }
 
-            gbuf_render_pipeline,
-            depth_texture,
-            normal_texture,
-            color_texture,
-            gbuf_bind_group,
-            lighting_render_pipeline,
+    /// Recreates the device and every GPU resource after the device is lost,
+    /// keeping CPU-side state and re-uploading meshes from their CPU copies.
+    async fn recover(self) -> anyhow::Result<State<'static>> {
+        tracing::warn!("Recreating GPU device and resources");
+        let window = self.window.clone();
+        let gpu_options = self.gpu_options.clone();
+        // Drop every GPU resource (including the surface) before creating new ones
+        let persistent = self.into_persistent();
 
-            camera,
-            camera_uniform,
-            camera_buffer,
-            camera_bind_group,
-            camera_controller: CameraController::new(5.),
+        let surface = gpu::create_surface(window.clone())?;
+        let mut state = State::new(window, surface, gpu_options, persistent.mesh).await?;
+        state.camera = persistent.camera;
+        state.camera.update_aspect(state.config.width as f32 / state.config.height as f32);
+        state.camera_controller = persistent.camera_controller;
+        state.time_of_day = persistent.time_of_day;
+        state.torch = persistent.torch;
+        // The model already faded in the first time it loaded
+        if let Some(model) = &mut state.model {
+            model.fade = 1.0;
+        }
+        state.graphics = persistent.graphics;
+        state.gameplay = persistent.gameplay;
+        state.events = persistent.events;
+        state.player_save = persistent.player_save;
+        state.achievements = persistent.achievements;
+        state.stats = persistent.stats;
+        state.hints = persistent.hints;
+        state.effects = persistent.effects;
+        state.show_collision_debug = persistent.show_collision_debug;
+        state.show_memory_stats = persistent.show_memory_stats;
+        state.paused = persistent.paused;
+        state.menu = persistent.menu;
+        state.log_viewer = persistent.log_viewer;
+        Ok(state)
+    }
 
-            model
+    fn set_loading_stage(&mut self, stage: LoadingStage) {
+        if let Some(loading) = &mut self.loading {
+            loading.set_stage(stage);
         }
     }
 
+    fn finish_loading(&mut self, mesh: MeshData) {
+        self.model = Some(Model::new(&self.device, mesh));
+        self.loading = None;
+        self.events.publish(GameEvent::WorldLoaded);
+    }
+
     fn get_window(&self) -> &Window {
         &self.window
     }
@@ -369,44 +715,511 @@ impl<'a> State<'a> {
             self.camera_uniform.update_view_proj(&self.camera);
             self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
 
-            self.depth_texture = texture::Texture::create_gbuf_texture(&self.device, &self.config, "depth_texture", true);
-            self.normal_texture = texture::Texture::create_gbuf_texture(&self.device, &self.config, "normal_texture", false);
-            self.color_texture = texture::Texture::create_gbuf_texture(&self.device, &self.config, "color_texture", false);
+            self.create_scene_targets();
         }
     }
 
+    /// Recreates everything drawn at the render scale, after a resize or a
+    /// change to the scale.
+    fn create_scene_targets(&mut self) {
+        let scale = self.auto_quality.scale(&self.graphics);
+        let config = RenderScale::scaled_config(&self.device, &self.config, scale);
+        self.depth_texture = texture::Texture::create_gbuf_texture(&self.device, &config, "depth_texture", true);
+        self.normal_texture = texture::Texture::create_gbuf_texture(&self.device, &config, "normal_texture", false);
+        self.color_texture = texture::Texture::create_gbuf_texture(&self.device, &config, "color_texture", false);
+        self.gbuf_bind_group = Self::create_gbuf_bind_group(
+            &self.device, &self.gbuf_bind_group_layout, &self.normal_texture, &self.color_texture, &self.depth_texture
+        );
+        self.scene_texture = texture::Texture::create_render_target(&self.device, &config, "scene_texture", Texture::HDR_FORMAT);
+        self.planar_reflection.resize(&self.device, &config);
+        self.ssr.resize(&self.device, &self.scene_texture);
+        self.post_texture = texture::Texture::create_render_target(&self.device, &config, "post_texture", Texture::HDR_FORMAT);
+        self.exposure.resize(&self.device, &self.post_texture);
+        self.water.resize(&self.device, &config, &self.planar_reflection.texture);
+        self.render_scale.resize(&self.device, &self.config, scale, &self.depth_texture);
+    }
+
+    /// The lighting shader variant for an ambient occlusion setting.
+    fn lighting_defines(ambient_occlusion: AmbientOcclusion) -> ShaderDefines {
+        ShaderDefines::new()
+            .set("SSAO", ambient_occlusion != AmbientOcclusion::Off)
+            .set("BENT_NORMALS", ambient_occlusion == AmbientOcclusion::BentNormals)
+    }
+
+    fn player_aabb(&self) -> Aabb {
+        let feet = self.camera.position() - cgmath::Vector3::new(0.0, PLAYER_EYE_HEIGHT, 0.0);
+        Aabb::new(
+            feet + cgmath::Vector3::new(-PLAYER_HALF_WIDTH, 0.0, -PLAYER_HALF_WIDTH),
+            feet + cgmath::Vector3::new(PLAYER_HALF_WIDTH, PLAYER_HEIGHT, PLAYER_HALF_WIDTH),
+        )
+    }
+
     fn update(&mut self, delta_time: f32) {
+        let _span = tracing::info_span!("update").entered();
+        // Unfocused frames are slowed down on purpose, so don't count them
+        if !self.paused {
+            self.auto_quality.update(delta_time, self.work_time, &self.graphics);
+        }
+        // Everything still runs while paused, just without time passing
+        let delta_time = if self.paused { 0.0 } else { delta_time };
+
+        crash::set_settings(self.graphics, self.gameplay);
+        self.effects.update(delta_time);
+        self.camera_controller.speed_multiplier = self.effects.speed_multiplier();
+        if self.menu.is_some() {
+            self.camera_controller.turn(MainMenu::CAMERA_TURN_SPEED * delta_time);
+        }
+        let previous_position = self.camera.position();
         self.camera_controller.update_camera(&mut self.camera, delta_time);
         self.camera_uniform.update_view_proj(&self.camera);
         self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+        // The menu's turning world isn't the player's doing
+        if self.in_game() {
+            self.publish_movement(previous_position);
+        }
+
+        let was_day = self.time_of_day.sun_direction().y > 0.0;
+        self.time_of_day.advance(delta_time);
+        let is_day = self.time_of_day.sun_direction().y > 0.0;
+        if is_day != was_day && self.in_game() {
+            self.events.publish(GameEvent::DaylightChanged { day: is_day });
+        }
+        self.water.update(delta_time);
+        if let Some(model) = &mut self.model {
+            model.update(delta_time);
+        }
+
+        self.torch.update(delta_time);
+        let point_lights: Vec<_> = self.torch.light(&self.camera).into_iter().collect();
+        self.lights.prepare(&self.queue, &point_lights);
+        let sky_colors = self.sky_gradient.sample(self.time_of_day.time);
+        let sky_uniform = SkyUniform::new(&self.time_of_day, &sky_colors, FOG_START, FOG_END);
+        self.queue.write_buffer(&self.sky_buffer, 0, bytemuck::cast_slice(&[sky_uniform]));
+        let godrays = if self.graphics.godrays { sky_colors.godrays } else { 0.0 };
+        self.godrays.prepare(&self.queue, godrays);
+        let min_exposure = self.effects.min_exposure(self.graphics.min_exposure).min(self.graphics.max_exposure);
+        self.exposure.prepare(&self.queue, delta_time, min_exposure, &self.graphics);
+
+        let player = self.player_aabb();
+        self.collision_log.clear();
+        if let Some(model) = &self.model {
+            self.collision_log.test(&player, &model.mesh.bounds);
+        }
+
+        if self.show_collision_debug {
+            self.draw_collision_debug(&player);
+        }
+        self.picker.update(&self.device);
+        if self.in_game() {
+            self.draw_target();
+            self.stats.update(delta_time);
+        }
+
+        self.save_timer -= delta_time;
+        // Achievements and hints only follow what happens in the world
+        let unlocked = self.in_game() && self.achievements.update(delta_time);
+        let hint_completed = self.in_game() && self.hints.update();
+        if unlocked || hint_completed || self.save_timer <= 0.0 {
+            self.write_save();
+        }
+        let show_hints = self.gameplay.hints && self.in_game();
+        self.hints.draw(&mut self.overlay, self.size, show_hints);
+        self.achievements.draw(&mut self.overlay, self.size);
+        self.stats.draw(&mut self.overlay, self.size);
+        self.effects.draw(&mut self.overlay, self.size);
+
+        self.memory.update(self.memory_usage(), self.graphics.memory_budget);
+        if self.show_memory_stats {
+            self.memory.draw(&mut self.overlay, self.size);
+        } else if self.memory.over_budget() {
+            self.memory.draw_warning(&mut self.overlay, self.size);
+        }
+
+        if let Some(menu) = &mut self.menu {
+            menu.sync(&self.graphics, &self.gameplay);
+            menu.draw(&mut self.overlay, self.size);
+        }
+        if self.paused {
+            self.draw_paused();
+        }
+        if let Some(loading) = &self.loading {
+            loading.draw(&mut self.overlay, self.size);
+        }
+        self.log_viewer.draw(&mut self.overlay, self.size.width as f32);
+        self.gpu_errors.draw_overlay(&mut self.overlay, self.size);
+    }
+
+    /// Whether the player is in the world, rather than the menu or the
+    /// loading screen.
+    fn in_game(&self) -> bool {
+        self.menu.is_none() && self.loading.is_none()
+    }
+
+    fn draw_paused(&mut self) {
+        const TEXT_SCALE: f32 = 4.0;
+        let (width, height) = (self.size.width as f32, self.size.height as f32);
+        self.overlay.rect(0.0, 0.0, width, height, [0.0, 0.0, 0.0, 0.4]);
+        let text = "Paused";
+        // Glyphs are square, so a character is as wide as a line is high
+        let line_height = Overlay::line_height(TEXT_SCALE);
+        let x = (width - text.len() as f32 * line_height) / 2.0;
+        self.overlay.text(x, (height - line_height) / 2.0, text, TEXT_SCALE, [1.0, 1.0, 1.0, 1.0]);
+    }
+
+    /// Stops the world while the window is unfocused, and starts it again
+    /// once it's focused.
+    fn set_focused(&mut self, focused: bool) {
+        self.paused = !focused;
+        self.camera_controller.release_keys();
+        self.update_cursor();
+    }
+
+    /// Grabs and hides the cursor while playing, and frees it while paused
+    /// or in the menu.
+    fn update_cursor(&self) {
+        let window = &self.window;
+        let playing = !self.paused && self.menu.is_none();
+        // Fullscreen leaves the cursor free, as F11 does
+        let grab = if playing && window.fullscreen().is_none() { CursorGrabMode::Confined } else { CursorGrabMode::None };
+        if let Err(e) = window.set_cursor_grab(grab) {
+            tracing::warn!("Failed to set cursor grab to {:?}: {}", grab, e);
+        }
+        window.set_cursor_visible(!playing);
+    }
+
+    fn open_menu(&mut self) {
+        self.menu = Some(MainMenu::new(self.gpu_report.lines()));
+        self.camera_controller.release_keys();
+        self.update_cursor();
+    }
+
+    fn close_menu(&mut self) {
+        self.menu = None;
+        self.update_cursor();
+    }
+
+    /// Passes input to the menu, if it's open, and acts on what it chose.
+    fn menu_input(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        input: impl FnOnce(&mut MainMenu, MenuSettings, winit::dpi::PhysicalSize<u32>) -> Option<MenuAction>
+    ) {
+        let Some(menu) = &mut self.menu else {
+            return;
+        };
+        let action = input(menu, MenuSettings {
+            graphics: &mut self.graphics,
+            gameplay: &mut self.gameplay,
+        }, self.size);
+        self.apply_menu_action(action, event_loop);
+    }
+
+    fn apply_menu_action(&mut self, action: Option<MenuAction>, event_loop: &ActiveEventLoop) {
+        match action {
+            Some(MenuAction::Play) => self.close_menu(),
+            Some(MenuAction::Quit) => {
+                tracing::info!("Quit was chosen in the menu; stopping");
+                event_loop.exit();
+            }
+            None => {}
+        }
+    }
+
+    /// Copies progress into the save and writes it.
+    fn write_save(&mut self) {
+        self.save_timer = SAVE_INTERVAL;
+        self.player_save.achievements = self.achievements.unlocked();
+        self.player_save.stats = self.stats.stats.clone();
+        self.player_save.completed_hints = self.hints.completed();
+        self.player_save.status_effects = self.effects.active();
+        if let Err(e) = self.player_save.write() {
+            tracing::error!("Failed to write the save file: {}", e);
+        }
+    }
+
+    fn publish_movement(&self, previous_position: cgmath::Point3<f32>) {
+        let position = self.camera.position();
+        if position != previous_position {
+            self.events.publish(GameEvent::PlayerMoved { from: previous_position, to: position });
+        }
+        let submerged = position.y < self.water.level;
+        if submerged != (previous_position.y < self.water.level) {
+            self.events.publish(GameEvent::Submerged { submerged });
+        }
+    }
+
+    /// Memory held by each part of the renderer, largest allocations only.
+    fn memory_usage(&self) -> Vec<(&'static str, MemoryUsage)> {
+        let mut usage = vec![
+            ("G-buffer", [&self.depth_texture, &self.normal_texture, &self.color_texture]
+                .into_iter()
+                .map(|texture| MemoryUsage::texture(&texture.texture))
+                .sum::<MemoryUsage>() + self.render_scale.memory()),
+            ("scene targets", MemoryUsage::texture(&self.scene_texture.texture) + MemoryUsage::texture(&self.post_texture.texture)),
+            ("exposure", self.exposure.memory()),
+            ("water", self.water.memory()),
+            ("planar reflect", self.planar_reflection.memory()),
+            ("lights", self.lights.memory()),
+            ("draw data", self.draw_data.memory()),
+            ("debug draw", self.debug_draw.memory()),
+            ("overlay", self.overlay.memory()),
+        ];
+        if let Some(model) = &self.model {
+            usage.push(("model", model.memory()));
+        }
+        usage
+    }
+
+    /// Draws the crosshair, and outlines the hitbox of what's under it if
+    /// it's in reach. The model is the only thing that can be targeted.
+    fn draw_target(&mut self) {
+        const SIZE: f32 = 10.0;
+        const THICKNESS: f32 = 2.0;
+        /// How far the outline sits outside the hitbox, so it isn't hidden
+        /// where the model touches it.
+        const OUTLINE_MARGIN: f32 = 0.02;
+
+        let (x, y) = (self.size.width as f32 / 2.0, self.size.height as f32 / 2.0);
+        let color = [1.0, 1.0, 1.0, 0.6];
+        self.overlay.rect(x - SIZE / 2.0, y - THICKNESS / 2.0, SIZE, THICKNESS, color);
+        self.overlay.rect(x - THICKNESS / 2.0, y - SIZE / 2.0, THICKNESS, SIZE, color);
+
+        let Some(model) = &self.model else { return };
+        let bounds = model.mesh.bounds;
+        // Inside the hitbox, the outline would just surround the screen
+        let in_reach = bounds.ray_distance(self.camera.position(), self.camera.forward())
+            .is_some_and(|distance| distance > 0.0 && distance <= REACH);
+        // The hitbox is loose, so check the model is really under the
+        // crosshair. It's draw 0. The forward renderer has no G-buffer to
+        // pick from, so there the hitbox has to do.
+        let under_crosshair = self.graphics.forward || self.picker.picked() == Some(0);
+        let in_reach = in_reach && under_crosshair;
+        if in_reach {
+            let margin = cgmath::Vector3::new(OUTLINE_MARGIN, OUTLINE_MARGIN, OUTLINE_MARGIN);
+            debug_draw::draw_aabb(&Aabb::new(bounds.min - margin, bounds.max + margin), [0.8, 0.8, 0.8]);
+        }
+    }
+
+    /// Draws the player's box in white and every box it was tested against
+    /// in green (clear) or red (touching).
+    fn draw_collision_debug(&self, player: &Aabb) {
+        debug_draw::draw_aabb(player, [1.0, 1.0, 1.0]);
+        for (aabb, result) in &self.collision_log.tests {
+            let (color, label) = match result {
+                CollisionResult::Clear => ([0.2, 1.0, 0.2], "clear"),
+                CollisionResult::Touching => ([1.0, 0.2, 0.2], "touching"),
+            };
+            debug_draw::draw_aabb(aabb, color);
+            debug_draw::draw_text_3d(aabb.max, label, color);
+        }
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
-        
+        let _span = tracing::info_span!("render").entered();
+
+        if self.auto_quality.scale(&self.graphics) != self.render_scale.scale() {
+            self.create_scene_targets();
+        }
+
+        self.debug_draw.prepare(&self.device, &self.queue, &self.camera);
+        self.overlay.prepare(&self.device, &self.queue, self.size);
+        let draws: Vec<_> = self.model.iter().enumerate().map(|(i, model)| model.draw_uniform(i as u32 + 1)).collect();
+        self.draw_data.prepare(&self.device, &self.queue, &draws);
+        if self.graphics.forward {
+            return self.render_forward();
+        }
+        self.ssr.prepare(&self.queue, self.graphics.reflections);
+        self.water.prepare(&self.queue, self.graphics.reflections);
+        let planar_reflections = self.graphics.reflections == ReflectionMode::Planar;
+        if planar_reflections {
+            self.planar_reflection.prepare(&self.queue, &self.camera, self.water.level);
+        }
+
+        // Everything up to tonemapping is submitted before waiting for a
+        // swapchain image, so the GPU can start on it while we wait.
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder"),
+            label: Some("Scene Encoder"),
         });
  
-        // Create the renderpass which will clear the screen.
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render Pass"),
+        // Fill the G-buffer with the scene's normals, colors and depth.
+        let mut gbuf_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("G-Buffer Pass"),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &self.normal_texture.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &self.color_texture.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        gbuf_pass.set_pipeline(&self.gbuf_render_pipeline);
+        gbuf_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        for (i, model) in self.model.iter().enumerate() {
+            self.draw_data.bind(&mut gbuf_pass, 1, i);
+            gbuf_pass.draw_model(model);
+        }
+        drop(gbuf_pass);
+        let size = self.color_texture.texture.size();
+        self.picker.copy(&mut encoder, &self.color_texture, size.width / 2, size.height / 2);
+
+        // Light the G-buffer into the scene texture with a full-screen triangle.
+        let mut lighting_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Lighting Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.scene_texture.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        let lighting_defines = Self::lighting_defines(self.graphics.ambient_occlusion);
+        lighting_pass.set_pipeline(self.lighting_pipelines.get(&lighting_defines));
+        lighting_pass.set_bind_group(0, &self.gbuf_bind_group, &[]);
+        lighting_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+        lighting_pass.set_bind_group(2, &self.sky_bind_group, &[]);
+        lighting_pass.set_bind_group(3, &self.lights.bind_group, &[]);
+        lighting_pass.draw(0..3, 0..1);
+        drop(lighting_pass);
+
+        if planar_reflections {
+            self.planar_reflection.render(&mut encoder, &self.sky_bind_group, &self.draw_data, &self.model);
+        }
+        self.water.render(&mut encoder, &self.scene_texture, &self.gbuf_bind_group, &self.camera_bind_group, &self.sky_bind_group);
+
+        // Add reflections to the lit scene, then add light shafts on top.
+        let mut composite_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.post_texture.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        self.ssr.draw(&mut composite_pass, &self.gbuf_bind_group, &self.camera_bind_group, &self.sky_bind_group);
+        self.godrays.draw(&mut composite_pass, &self.gbuf_bind_group, &self.camera_bind_group, &self.sky_bind_group);
+        drop(composite_pass);
+
+        self.exposure.measure(&mut encoder);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.picker.submitted();
+
+        let output = self.acquire()?;
+        let view = self.surface_view(&output);
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Present Encoder"),
+        });
+
+        // Expose and tonemap the finished scene onto the screen.
+        let mut tonemap_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tonemap Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                 view: &view,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.0,
-                        g: 0.0,
-                        b: 0.0,
-                        a: 1.0,
-                    }),
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        self.exposure.draw(&mut tonemap_pass);
+        drop(tonemap_pass);
+
+        self.render_scale.copy_depth(&mut encoder);
+        self.draw_overlays(&mut encoder, &view);
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.present(output);
+
+        Ok(())
+    }
+
+    /// Gets the next swapchain image. With vsync this usually waits for the
+    /// display, so the wait is left out of the work time.
+    fn acquire(&mut self) -> Result<wgpu::SurfaceTexture, wgpu::SurfaceError> {
+        let start = std::time::Instant::now();
+        let output = self.surface.get_current_texture();
+        self.swapchain_wait += start.elapsed();
+        output
+    }
+
+    /// Presents the frame. Some platforms wait for vsync here instead.
+    fn present(&mut self, output: wgpu::SurfaceTexture) {
+        let start = std::time::Instant::now();
+        output.present();
+        self.swapchain_wait += start.elapsed();
+    }
+
+    /// Records the work time of a frame that took `elapsed` in all.
+    fn end_frame(&mut self, elapsed: std::time::Duration) {
+        self.work_time = elapsed.saturating_sub(std::mem::take(&mut self.swapchain_wait)).as_secs_f32();
+    }
+
+    /// An sRGB view of the frame, even when the surface format isn't sRGB.
+    fn surface_view(&self, output: &wgpu::SurfaceTexture) -> wgpu::TextureView {
+        output.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.config.format.add_srgb_suffix()),
+            ..Default::default()
+        })
+    }
+
+    /// The safe-mode path: the forward renderer straight to the screen.
+    fn render_forward(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let output = self.acquire()?;
+        let view = self.surface_view(&output);
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Forward Encoder"),
+        });
+
+        // Nothing draws the sky, so the background is its horizon color.
+        let [r, g, b] = self.sky_gradient.sample(self.time_of_day.time).fog.map(f64::from);
+        let mut forward_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Forward Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a: 1.0 }),
                     store: wgpu::StoreOp::Store,
                 },
             })],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture.view,
+                view: &self.render_scale.depth_texture.view,
                 depth_ops: Some(wgpu::Operations {
                     load: wgpu::LoadOp::Clear(1.0),
                     store: wgpu::StoreOp::Store,
@@ -416,32 +1229,170 @@ impl<'a> State<'a> {
             occlusion_query_set: None,
             timestamp_writes: None,
         });
+        self.forward.draw(&mut forward_pass, &self.camera_bind_group, &self.sky_bind_group, &self.draw_data, &self.model);
+        drop(forward_pass);
 
-        // If you wanted to call any drawing commands, they would go here.
-        render_pass.set_pipeline(&self.gbuf_render_pipeline);
-
-        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-        
-        render_pass.draw_model(&self.model);
-
-        // End the renderpass.
-        drop(render_pass);
+        self.draw_overlays(&mut encoder, &view);
 
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        self.present(output);
 
         Ok(())
     }
+
+    /// Draws debug geometry and the 2D overlay on top of the finished frame.
+    fn draw_overlays(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        // Debug geometry is depth-tested against the scene's depth.
+        let mut debug_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.render_scale.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        self.debug_draw.draw(&mut debug_pass, &self.camera_bind_group);
+        drop(debug_pass);
+
+        // 2D elements are drawn in their own pass since they don't use the depth buffer
+        let mut overlay_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        self.overlay.draw(&mut overlay_pass);
+        drop(overlay_pass);
+    }
+}
+
+/// Progress reports from the startup task.
+enum StartupMessage {
+    Stage(LoadingStage),
+    Renderer(Box<State<'static>>),
+    Assets(MeshData),
+    Failed(anyhow::Error),
+}
+
+/// Creates the renderer and loads assets, reporting each step through `sender`.
+async fn startup(
+    window: Arc<Window>,
+    surface: (wgpu::Instance, wgpu::Surface<'static>),
+    gpu_options: GpuOptions,
+    sender: mpsc::Sender<StartupMessage>
+) {
+    // Sending only fails if the app has already exited, so errors are ignored
+    let _ = sender.send(StartupMessage::Stage(LoadingStage::GpuInit));
+    match State::new(window, surface, gpu_options, None).await {
+        Ok(state) => {
+            let _ = sender.send(StartupMessage::Renderer(Box::new(state)));
+        }
+        Err(e) => {
+            let _ = sender.send(StartupMessage::Failed(e));
+            return;
+        }
+    }
+
+    let _ = sender.send(StartupMessage::Stage(LoadingStage::Assets));
+    let _ = sender.send(match MeshData::load("teapot.obj").await {
+        Ok(mesh) => StartupMessage::Assets(mesh),
+        Err(e) => StartupMessage::Failed(e),
+    });
 }
 
 #[derive(Default)]
-struct App<'a> {
-    state: Option<State<'a>>,
+struct App {
+    gpu_options: GpuOptions,
+    /// The last run's crash report, shown on the first menu.
+    crash_report: Option<std::path::PathBuf>,
+    startup: Option<mpsc::Receiver<StartupMessage>>,
+    state: Option<State<'static>>,
     window: Option<Arc<Window>>,
     last_draw: Option<std::time::Instant>
 }
 
-impl<'a> ApplicationHandler for App<'a> {
+impl App {
+    /// Handles everything the startup task has reported since the last call.
+    fn poll_startup(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(receiver) = &self.startup else {
+            return;
+        };
+
+        let mut finished = false;
+        loop {
+            match receiver.try_recv() {
+                Ok(StartupMessage::Stage(stage)) => match &mut self.state {
+                    Some(state) => state.set_loading_stage(stage),
+                    None => tracing::info!("Startup stage: {}", stage.label()),
+                },
+                Ok(StartupMessage::Renderer(mut state)) => {
+                    if let (Some(report), Some(menu)) = (self.crash_report.take(), &mut state.menu) {
+                        menu.set_notice(vec![
+                            "The last run crashed. Its crash report is at".to_string(),
+                            report.display().to_string(),
+                        ]);
+                    }
+                    state.update_cursor();
+                    state.get_window().request_redraw();
+                    self.state = Some(*state);
+                }
+                Ok(StartupMessage::Assets(mesh)) => {
+                    if let Some(state) = &mut self.state {
+                        state.finish_loading(mesh);
+                    }
+                }
+                Ok(StartupMessage::Failed(e)) => {
+                    tracing::error!("Failed to initialize: {:#}", e);
+                    event_loop.exit();
+                    finished = true;
+                    break;
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    // Startup only hangs up before it's done if it panicked
+                    if self.state.as_ref().is_none_or(|state| state.loading.is_some()) {
+                        tracing::error!("Failed to initialize: startup exited before it finished");
+                        event_loop.exit();
+                    }
+                    finished = true;
+                    break;
+                }
+            }
+        }
+        if finished {
+            self.startup = None;
+        }
+    }
+}
+
+impl ApplicationHandler for App {
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(state) = &mut self.state {
+            state.write_save();
+        }
+    }
+
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         // Create window object
         let window = Arc::new(
@@ -450,8 +1401,26 @@ impl<'a> ApplicationHandler for App<'a> {
                 .unwrap(),
         );
 
-        let state = pollster::block_on(State::new(window.clone()));
-        self.state = Some(state);
+        let surface = match gpu::create_surface(window.clone()) {
+            Ok(surface) => surface,
+            Err(e) => {
+                tracing::error!("Failed to create surface: {:#}", e);
+                event_loop.exit();
+                return;
+            }
+        };
+
+        // Initialize off the event loop so the window stays responsive
+        let (sender, receiver) = mpsc::channel();
+        self.startup = Some(receiver);
+        let task = startup(window.clone(), surface, self.gpu_options.clone(), sender);
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                wasm_bindgen_futures::spawn_local(task);
+            } else {
+                std::thread::spawn(move || pollster::block_on(task));
+            }
+        }
         self.window = Some(window.clone());
 
         window.set_cursor_grab(CursorGrabMode::Confined).expect("Failed to grab cursor");
@@ -460,16 +1429,36 @@ impl<'a> ApplicationHandler for App<'a> {
         window.request_redraw();
     }
 
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        // The wait for the next unfocused frame is over
+        if let (StartCause::ResumeTimeReached { .. }, Some(state)) = (cause, &self.state) {
+            state.get_window().request_redraw();
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.poll_startup(event_loop);
+    }
+
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
-        let state = self.state.as_mut().unwrap();
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
         match event {
             WindowEvent::CloseRequested => {
-                println!("The close button was pressed; stopping");
+                tracing::info!("The close button was pressed; stopping");
                 event_loop.exit();
             }
             WindowEvent::RedrawRequested => {
-                // Tell winit we want another frame after this
-                state.get_window().request_redraw();
+                // Ask for another frame after this one. Unfocused windows
+                // only get a few frames a second, from `new_events`; on the
+                // web they stop drawing until focused again.
+                if state.paused {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    event_loop.set_control_flow(ControlFlow::WaitUntil(std::time::Instant::now() + UNFOCUSED_FRAME_TIME));
+                } else {
+                    state.get_window().request_redraw();
+                }
 
                 let delta_time = match self.last_draw {
                     Some(last) => {
@@ -484,6 +1473,7 @@ impl<'a> ApplicationHandler for App<'a> {
                     }
                 };
                 
+                let frame_start = std::time::Instant::now();
                 state.update(delta_time);
                 match state.render() {
                     Ok(_) => {}
@@ -491,43 +1481,179 @@ impl<'a> ApplicationHandler for App<'a> {
                     Err(
                         wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated,
                     ) => state.resize(state.size),
-                    // The system is out of memory, we should probably quit
-                    Err(wgpu::SurfaceError::OutOfMemory | wgpu::SurfaceError::Other) => {
-                        log::error!("OutOfMemory");
-                        event_loop.exit();
+                    // The system is out of memory or the device is in a bad state; try starting over
+                    Err(error @ (wgpu::SurfaceError::OutOfMemory | wgpu::SurfaceError::Other)) => {
+                        tracing::error!("Failed to render: {}", error);
+                        state.gpu_errors.request_recovery();
                     }
 
                     // This happens when the a frame takes too long to present
                     Err(wgpu::SurfaceError::Timeout) => {
-                        log::warn!("Surface timeout")
+                        tracing::warn!("Surface timeout")
+                    }
+                }
+                state.end_frame(frame_start.elapsed());
+
+                if state.gpu_errors.needs_recovery() {
+                    let old_state = self.state.take().unwrap();
+                    match pollster::block_on(old_state.recover()) {
+                        Ok(state) => self.state = Some(state),
+                        Err(e) => {
+                            tracing::error!("Failed to recover from GPU error: {:#}", e);
+                            event_loop.exit();
+                        }
                     }
                 }
             }
+            WindowEvent::Focused(focused) => {
+                tracing::debug!("Window focused: {}", focused);
+                state.set_focused(focused);
+                if focused {
+                    event_loop.set_control_flow(ControlFlow::Poll);
+                    state.get_window().request_redraw();
+                }
+            }
             WindowEvent::Resized(size) => {
                 // Reconfigures the size of the surface. We do not re-render
                 // here as this event is always followed up by redraw request.
                 state.resize(size);
             }
-            WindowEvent::KeyboardInput { event, .. } if event.physical_key == PhysicalKey::Code(KeyCode::Escape) => {
-                // If the Escape key is pressed, we exit the application.
-                println!("Escape key pressed; stopping");
-                event_loop.exit();
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::Escape), state: ElementState::Pressed, repeat: false, ..
+            }, .. } => {
+                // Escape opens the menu, or backs out of it
+                match &mut state.menu {
+                    Some(menu) => if menu.back() {
+                        state.close_menu();
+                    },
+                    None => state.open_menu(),
+                }
             }
             WindowEvent::KeyboardInput { event: KeyEvent {
                 physical_key: PhysicalKey::Code(KeyCode::F11), state: ElementState::Pressed, repeat: false, ..
             }, .. } => {
                 // Toggle fullscreen mode
-                if let Some(window) = self.window.as_ref() {
-                    if window.fullscreen().is_some() {
-                        window.set_fullscreen(None);
-                        window.set_cursor_grab(CursorGrabMode::Confined).expect("Failed to grab cursor");
-                    } else {
-                        window.set_fullscreen(Some(winit::window::Fullscreen::Borderless(None)));
-                        window.set_cursor_grab(CursorGrabMode::None).expect("Failed to release cursor");
-                    }
+                let window = state.get_window();
+                if window.fullscreen().is_some() {
+                    window.set_fullscreen(None);
+                } else {
+                    window.set_fullscreen(Some(winit::window::Fullscreen::Borderless(None)));
                 }
+                state.update_cursor();
             }
-            WindowEvent::CursorMoved { .. } => {
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::F3), state: ElementState::Pressed, repeat: false, ..
+            }, .. } => {
+                // Toggle collision box visualization
+                state.show_collision_debug = !state.show_collision_debug;
+            }
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::F6), state: ElementState::Pressed, repeat: false, ..
+            }, .. } => {
+                state.show_memory_stats = !state.show_memory_stats;
+            }
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::F1), state: ElementState::Pressed, repeat: false, ..
+            }, .. } => {
+                state.gameplay.hints = !state.gameplay.hints;
+                tracing::info!("Tutorial hints: {}", state.gameplay.hints);
+            }
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::F9), state: ElementState::Pressed, repeat: false, ..
+            }, .. } => {
+                state.graphics.forward = !state.graphics.forward;
+                tracing::info!("Forward renderer: {}", state.graphics.forward);
+            }
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::F7), state: ElementState::Pressed, repeat: false, ..
+            }, .. } => {
+                state.stats.open = !state.stats.open;
+            }
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::F8), state: ElementState::Pressed, repeat: false, ..
+            }, .. } => {
+                // Nothing in the world grants effects yet, so this is how to try them
+                for effect in StatusEffect::ALL {
+                    state.effects.add(effect, DEBUG_EFFECT_DURATION);
+                }
+            }
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::KeyT), state: ElementState::Pressed, repeat: false, ..
+            }, .. } if state.in_game() => {
+                // Hold or put away the torch. There's no inventory yet, so it's a toggle.
+                state.torch.held = !state.torch.held;
+                state.events.publish(GameEvent::TorchToggled { held: state.torch.held });
+            }
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::F4), state: ElementState::Pressed, repeat: false, ..
+            }, .. } => {
+                // Cycle reflection quality
+                state.graphics.reflections = state.graphics.reflections.next();
+                tracing::info!("Reflections: {:?}", state.graphics.reflections);
+            }
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::F5), state: ElementState::Pressed, repeat: false, ..
+            }, .. } => {
+                // Toggle light shafts
+                state.graphics.godrays = !state.graphics.godrays;
+                tracing::info!("Godrays: {}", state.graphics.godrays);
+            }
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(key @ (KeyCode::BracketLeft | KeyCode::BracketRight)),
+                state: ElementState::Pressed, ..
+            }, .. } => {
+                // Step the time of day back or forward by an hour
+                state.time_of_day.skip_hours(if key == KeyCode::BracketLeft { -1.0 } else { 1.0 });
+            }
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::F2), state: ElementState::Pressed, repeat: false, ..
+            }, .. } => {
+                state.log_viewer.open = !state.log_viewer.open;
+            }
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(key @ (KeyCode::Digit1 | KeyCode::Digit2 | KeyCode::Digit3 | KeyCode::Digit4 | KeyCode::Digit5)),
+                state: ElementState::Pressed, ..
+            }, .. } if state.log_viewer.open => {
+                // Pick the most verbose level shown in the log viewer
+                state.log_viewer.max_level = match key {
+                    KeyCode::Digit1 => tracing::Level::ERROR,
+                    KeyCode::Digit2 => tracing::Level::WARN,
+                    KeyCode::Digit3 => tracing::Level::INFO,
+                    KeyCode::Digit4 => tracing::Level::DEBUG,
+                    _ => tracing::Level::TRACE,
+                };
+            }
+            WindowEvent::KeyboardInput { event: KeyEvent {
+                physical_key: PhysicalKey::Code(key), state: ElementState::Pressed, ..
+            }, .. } if state.menu.is_some() => {
+                state.menu_input(event_loop, |menu, settings, _| menu.key(key, settings));
+            }
+            WindowEvent::CursorMoved { position, .. } if state.menu.is_some() => {
+                state.menu_input(event_loop, |menu, settings, size| {
+                    menu.cursor_moved(position.x as f32, position.y as f32, size, settings)
+                });
+            }
+            WindowEvent::MouseInput { state: button_state, button: MouseButton::Left, .. } if state.menu.is_some() => {
+                state.menu_input(event_loop, |menu, settings, size| match button_state {
+                    ElementState::Pressed => menu.mouse_down(size, settings),
+                    ElementState::Released => {
+                        menu.mouse_up();
+                        None
+                    }
+                });
+            }
+            WindowEvent::MouseWheel { delta, .. } if state.menu.is_some() => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    // Roughly one row per line
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 48.0,
+                };
+                state.menu_input(event_loop, |menu, _, size| {
+                    menu.scroll(lines, size);
+                    None
+                });
+            }
+            WindowEvent::CursorMoved { .. } if !state.paused => {
                 let center = winit::dpi::PhysicalPosition::new(
                     state.size.width as f64 / 2.0,
                     state.size.height as f64 / 2.0,
@@ -543,8 +1669,22 @@ impl<'a> ApplicationHandler for App<'a> {
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
 fn main() {
-    // wgpu uses `log` for logging, so initialize a logger with `env_logger`
-    env_logger::init();
+    // wgpu uses `log` for logging; the subscriber forwards its records along with ours
+    logging::init();
+    crash::install();
+
+    let args = cli::Args::parse();
+    #[cfg(not(target_arch = "wasm32"))]
+    if args.list_gpus {
+        gpu::list_adapters();
+        return;
+    }
+    let mut gpu_options = args.gpu_options();
+    let crash = session::begin();
+    if crash.is_some() && !gpu_options.safe_mode {
+        tracing::warn!("The last run didn't exit cleanly; starting in safe mode. F9 switches back to the full renderer");
+        gpu_options.safe_mode = true;
+    }
 
     let event_loop = EventLoop::new().unwrap();
 
@@ -560,6 +1700,7 @@ fn main() {
     // the background.
     // event_loop.set_control_flow(ControlFlow::Wait);
 
-    let mut app = App::default();
+    let mut app = App { gpu_options, crash_report: crash.and_then(|crash| crash.report), ..Default::default() };
     event_loop.run_app(&mut app).unwrap();
+    session::end();
 }
\ No newline at end of file
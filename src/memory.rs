@@ -0,0 +1,148 @@
+//! Accounting for how much memory each part of the renderer holds, shown in
+//! the memory overlay and checked against the memory budget in
+//! `GraphicsSettings`.
+
+use std::{iter::Sum, ops::Add};
+
+use crate::overlay::Overlay;
+
+/// Memory held by one subsystem. Sizes are what we asked for; drivers pad and
+/// align allocations, so real usage is somewhat higher.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MemoryUsage {
+    pub vertices: u64,
+    pub indices: u64,
+    pub buffer_bytes: u64,
+    pub texture_bytes: u64,
+    /// Data kept on the CPU side, like mesh data used for collision.
+    pub cpu_bytes: u64,
+}
+
+impl MemoryUsage {
+    pub fn buffer(buffer: &wgpu::Buffer) -> Self {
+        Self { buffer_bytes: buffer.size(), ..Default::default() }
+    }
+
+    /// Counts every mip level and array layer of `texture`.
+    pub fn texture(texture: &wgpu::Texture) -> Self {
+        let format = texture.format();
+        let (block_width, block_height) = format.block_dimensions();
+        let block_bytes = format.block_copy_size(None).unwrap_or(4) as u64;
+        let texture_bytes = (0..texture.mip_level_count())
+            .map(|level| {
+                let width = (texture.width() >> level).max(1).div_ceil(block_width) as u64;
+                let height = (texture.height() >> level).max(1).div_ceil(block_height) as u64;
+                width * height * block_bytes
+            })
+            .sum::<u64>()
+            * texture.depth_or_array_layers() as u64
+            * texture.sample_count() as u64;
+        Self { texture_bytes, ..Default::default() }
+    }
+
+    pub fn gpu_bytes(&self) -> u64 {
+        self.buffer_bytes + self.texture_bytes
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.gpu_bytes() + self.cpu_bytes
+    }
+}
+
+impl Add for MemoryUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            vertices: self.vertices + other.vertices,
+            indices: self.indices + other.indices,
+            buffer_bytes: self.buffer_bytes + other.buffer_bytes,
+            texture_bytes: self.texture_bytes + other.texture_bytes,
+            cpu_bytes: self.cpu_bytes + other.cpu_bytes,
+        }
+    }
+}
+
+impl Sum for MemoryUsage {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+fn megabytes(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+/// The latest usage of every subsystem, and whether it's over budget.
+#[derive(Default)]
+pub struct MemoryStats {
+    subsystems: Vec<(&'static str, MemoryUsage)>,
+    total: MemoryUsage,
+    budget: u64,
+    over_budget: bool,
+}
+
+impl MemoryStats {
+    const TEXT_SCALE: f32 = 1.0;
+
+    /// Replaces the stats with this frame's and warns when usage crosses
+    /// `budget`, in bytes.
+    pub fn update(&mut self, subsystems: Vec<(&'static str, MemoryUsage)>, budget: u64) {
+        self.total = subsystems.iter().map(|(_, usage)| *usage).sum();
+        self.subsystems = subsystems;
+        self.budget = budget;
+
+        let over_budget = self.total.total_bytes() > budget;
+        if over_budget && !self.over_budget {
+            tracing::warn!(
+                "Using {:.1} MiB, over the {:.1} MiB memory budget",
+                megabytes(self.total.total_bytes()), megabytes(budget)
+            );
+        } else if !over_budget && self.over_budget {
+            tracing::info!("Back under the memory budget");
+        }
+        self.over_budget = over_budget;
+    }
+
+    /// Whether usage is over budget. Anything that streams data in should
+    /// unload or lower detail sooner while this is true.
+    pub fn over_budget(&self) -> bool {
+        self.over_budget
+    }
+
+    /// A one-line warning in the top right corner, for when the table isn't
+    /// showing.
+    pub fn draw_warning(&self, overlay: &mut Overlay, size: winit::dpi::PhysicalSize<u32>) {
+        let text = format!(
+            "Over memory budget: {:.0}/{:.0} MiB (F6)",
+            megabytes(self.total.total_bytes()), megabytes(self.budget)
+        );
+        let char_width = Overlay::line_height(Self::TEXT_SCALE);
+        let left = size.width as f32 - text.len() as f32 * char_width - 8.0;
+        overlay.text(left, 4.0, &text, Self::TEXT_SCALE, [1.0, 0.4, 0.4, 1.0]);
+    }
+
+    /// A table of usage per subsystem in the top right corner.
+    pub fn draw(&self, overlay: &mut Overlay, size: winit::dpi::PhysicalSize<u32>) {
+        let line_height = Overlay::line_height(Self::TEXT_SCALE) + 2.0;
+        let mut lines = vec![format!("{:<16}{:>9}{:>9}{:>9}{:>9}", "memory", "verts", "indices", "GPU MiB", "CPU MiB")];
+        for (name, usage) in self.subsystems.iter().chain([("total", self.total)].iter()) {
+            lines.push(format!(
+                "{:<16}{:>9}{:>9}{:>9.1}{:>9.1}",
+                name, usage.vertices, usage.indices, megabytes(usage.gpu_bytes()), megabytes(usage.cpu_bytes)
+            ));
+        }
+        lines.push(format!("budget {:.0} MiB", megabytes(self.budget)));
+
+        // Glyphs are square, so a character is as wide as a line is high
+        let char_width = Overlay::line_height(Self::TEXT_SCALE);
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) as f32 * char_width;
+        let left = size.width as f32 - width - 8.0;
+        overlay.rect(left - 4.0, 0.0, width + 12.0, line_height * lines.len() as f32 + 4.0, [0.0, 0.0, 0.0, 0.6]);
+        for (i, line) in lines.iter().enumerate() {
+            let over = self.over_budget && i == lines.len() - 1;
+            let color = if over { [1.0, 0.4, 0.4, 1.0] } else { [1.0, 1.0, 1.0, 1.0] };
+            overlay.text(left, 2.0 + line_height * i as f32, line, Self::TEXT_SCALE, color);
+        }
+    }
+}
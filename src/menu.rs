@@ -0,0 +1,277 @@
+//! The main menu, shown over the world while the camera slowly turns. It's
+//! also where Escape goes during play. Each page is a `ui::Panel`, so items
+//! are chosen with the arrow keys and Enter, or with the mouse.
+
+use winit::keyboard::KeyCode;
+
+use crate::{overlay::Overlay, settings::{GameplaySettings, GraphicsSettings}, ui::{Panel, UiEvent, Widget}};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Page {
+    Main,
+    Settings,
+    /// Brightness and HDR output.
+    Display,
+    /// Resolution, automatic quality and the memory budget.
+    Performance,
+    /// What the game is running on, for bug reports.
+    System,
+}
+
+/// What the menu asks the game to do.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MenuAction {
+    Play,
+    Quit,
+}
+
+/// The settings a menu can change.
+pub struct MenuSettings<'a> {
+    pub graphics: &'a mut GraphicsSettings,
+    pub gameplay: &'a mut GameplaySettings,
+}
+
+// Widget indices on the main page
+const PLAY: usize = 0;
+const SETTINGS: usize = 1;
+
+// Widget indices on the settings page
+const REFLECTIONS: usize = 0;
+const GODRAYS: usize = 1;
+const AMBIENT_OCCLUSION: usize = 2;
+const RENDERER: usize = 3;
+const DISPLAY: usize = 4;
+const PERFORMANCE: usize = 5;
+const HINTS: usize = 6;
+const SYSTEM: usize = 7;
+
+// Widget indices on the display page
+const MAX_EXPOSURE: usize = 0;
+const GAMMA: usize = 1;
+const PAPER_WHITE: usize = 2;
+const PEAK_BRIGHTNESS: usize = 3;
+
+// Widget indices on the performance page
+const RENDER_SCALE: usize = 0;
+const AUTO_QUALITY: usize = 1;
+const TARGET_FPS: usize = 2;
+const MIN_RENDER_SCALE: usize = 3;
+const MEMORY_BUDGET: usize = 4;
+
+const BYTES_PER_MIB: f32 = 1024.0 * 1024.0;
+
+pub struct MainMenu {
+    page: Page,
+    panel: Panel,
+    /// The lines of the GPU report, for the System page.
+    system: Vec<String>,
+    /// Lines shown under the main page, like where the last crash report is.
+    notice: Vec<String>,
+}
+
+impl MainMenu {
+    /// Radians per second the camera turns behind the menu.
+    pub const CAMERA_TURN_SPEED: f32 = 0.05;
+    const NOTICE_SCALE: f32 = 2.0;
+
+    pub fn new(system: Vec<String>) -> Self {
+        let panel = Self::panel(Page::Main, &system);
+        Self { page: Page::Main, panel, system, notice: Vec::new() }
+    }
+
+    pub fn set_notice(&mut self, lines: Vec<String>) {
+        self.notice = lines;
+    }
+
+    /// The widgets on a page. Settings labels are filled in by `sync`.
+    fn panel(page: Page, system: &[String]) -> Panel {
+        match page {
+            Page::Main => Panel::new("VoxelGame", vec![
+                Widget::button("Play"),
+                Widget::button("Settings"),
+                Widget::button("Quit"),
+            ]),
+            Page::Settings => Panel::new("Settings", vec![
+                Widget::button("Reflections"),
+                Widget::button("Godrays"),
+                Widget::button("Ambient occlusion"),
+                Widget::button("Renderer"),
+                Widget::button("Display"),
+                Widget::button("Performance"),
+                Widget::button("Hints"),
+                Widget::button("System"),
+                Widget::button("Back"),
+            ]),
+            Page::Display => Panel::new("Display", vec![
+                Widget::slider("Max exposure", 1.0, 1.0, 8.0, 0.5),
+                Widget::slider("Gamma", 1.0, 0.5, 2.0, 0.1),
+                Widget::slider("Paper white", 200.0, 80.0, 400.0, 10.0),
+                Widget::slider("Peak", 1000.0, 400.0, 2000.0, 100.0),
+                Widget::button("Back"),
+            ]),
+            Page::Performance => Panel::new("Performance", vec![
+                Widget::slider("Scale", 1.0, 0.5, 2.0, 0.25),
+                Widget::button("Auto quality"),
+                Widget::slider("Target FPS", 60.0, 30.0, 240.0, 10.0),
+                Widget::slider("Min scale", 0.5, 0.5, 2.0, 0.25),
+                Widget::slider("Memory budget", 1024.0, 256.0, 8192.0, 256.0),
+                Widget::button("Back"),
+            ]),
+            Page::System => Panel::new("System", vec![
+                Widget::list("GPU", system.to_vec()),
+                Widget::button("Back"),
+            ]),
+        }
+    }
+
+    fn open(&mut self, page: Page, focus: usize) {
+        self.page = page;
+        self.panel = Self::panel(page, &self.system);
+        self.panel.focus(focus);
+    }
+
+    /// Shows the current settings, which can also be changed by hotkeys.
+    pub fn sync(&mut self, graphics: &GraphicsSettings, gameplay: &GameplaySettings) {
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        match self.page {
+            Page::Settings => {
+                let widgets = &mut self.panel.widgets;
+                widgets[REFLECTIONS].label = format!("Reflections: {:?}", graphics.reflections);
+                widgets[GODRAYS].label = format!("Godrays: {}", on_off(graphics.godrays));
+                widgets[AMBIENT_OCCLUSION].label = format!("AO: {:?}", graphics.ambient_occlusion);
+                widgets[RENDERER].label = format!("Renderer: {}", if graphics.forward { "Safe" } else { "Full" });
+                widgets[HINTS].label = format!("Hints: {}", on_off(gameplay.hints));
+            }
+            Page::Display => {
+                let widgets = &mut self.panel.widgets;
+                widgets[MAX_EXPOSURE].label = format!("Exposure {:.1}", graphics.max_exposure);
+                widgets[GAMMA].label = format!("Gamma {:.1}", graphics.gamma);
+                widgets[PAPER_WHITE].label = format!("White {:.0}", graphics.paper_white);
+                widgets[PEAK_BRIGHTNESS].label = format!("Peak {:.0}", graphics.peak_brightness);
+                self.panel.set_value(MAX_EXPOSURE, graphics.max_exposure);
+                self.panel.set_value(GAMMA, graphics.gamma);
+                self.panel.set_value(PAPER_WHITE, graphics.paper_white);
+                self.panel.set_value(PEAK_BRIGHTNESS, graphics.peak_brightness);
+            }
+            Page::Performance => {
+                let memory_budget = graphics.memory_budget as f32 / BYTES_PER_MIB;
+                let widgets = &mut self.panel.widgets;
+                widgets[RENDER_SCALE].label = format!("Scale {:.0}%", graphics.render_scale * 100.0);
+                widgets[AUTO_QUALITY].label = format!("Auto quality: {}", on_off(graphics.auto_quality));
+                widgets[TARGET_FPS].label = format!("Target {:.0} FPS", graphics.target_fps);
+                widgets[MIN_RENDER_SCALE].label = format!("Min scale {:.0}%", graphics.min_render_scale * 100.0);
+                widgets[MEMORY_BUDGET].label = format!("Memory {:.0} MiB", memory_budget);
+                self.panel.set_value(RENDER_SCALE, graphics.render_scale);
+                self.panel.set_value(TARGET_FPS, graphics.target_fps);
+                self.panel.set_value(MIN_RENDER_SCALE, graphics.min_render_scale);
+                self.panel.set_value(MEMORY_BUDGET, memory_budget);
+            }
+            Page::Main | Page::System => {}
+        }
+    }
+
+    /// Goes back a page. Returns whether the menu should close, which it
+    /// does from the main page.
+    pub fn back(&mut self) -> bool {
+        match self.page {
+            Page::Main => true,
+            Page::Settings => {
+                self.open(Page::Main, SETTINGS);
+                false
+            }
+            Page::Display => {
+                self.open(Page::Settings, DISPLAY);
+                false
+            }
+            Page::Performance => {
+                self.open(Page::Settings, PERFORMANCE);
+                false
+            }
+            Page::System => {
+                self.open(Page::Settings, SYSTEM);
+                false
+            }
+        }
+    }
+
+    fn handle(&mut self, event: Option<UiEvent>, settings: MenuSettings) -> Option<MenuAction> {
+        match (self.page, event?) {
+            (Page::Main, UiEvent::Pressed(PLAY)) => return Some(MenuAction::Play),
+            (Page::Main, UiEvent::Pressed(SETTINGS)) => self.open(Page::Settings, 0),
+            (Page::Main, UiEvent::Pressed(_)) => return Some(MenuAction::Quit),
+            (Page::Settings, UiEvent::Pressed(REFLECTIONS)) => settings.graphics.reflections = settings.graphics.reflections.next(),
+            (Page::Settings, UiEvent::Pressed(GODRAYS)) => settings.graphics.godrays = !settings.graphics.godrays,
+            (Page::Settings, UiEvent::Pressed(AMBIENT_OCCLUSION)) => {
+                settings.graphics.ambient_occlusion = settings.graphics.ambient_occlusion.next();
+            }
+            (Page::Settings, UiEvent::Pressed(RENDERER)) => settings.graphics.forward = !settings.graphics.forward,
+            (Page::Settings, UiEvent::Pressed(DISPLAY)) => self.open(Page::Display, 0),
+            (Page::Settings, UiEvent::Pressed(PERFORMANCE)) => self.open(Page::Performance, 0),
+            (Page::Settings, UiEvent::Pressed(HINTS)) => settings.gameplay.hints = !settings.gameplay.hints,
+            (Page::Settings, UiEvent::Pressed(SYSTEM)) => self.open(Page::System, 0),
+            (Page::Display, UiEvent::Changed(MAX_EXPOSURE)) => {
+                settings.graphics.max_exposure = self.panel.value(MAX_EXPOSURE);
+            }
+            (Page::Display, UiEvent::Changed(GAMMA)) => settings.graphics.gamma = self.panel.value(GAMMA),
+            (Page::Display, UiEvent::Changed(PAPER_WHITE)) => settings.graphics.paper_white = self.panel.value(PAPER_WHITE),
+            (Page::Display, UiEvent::Changed(PEAK_BRIGHTNESS)) => {
+                settings.graphics.peak_brightness = self.panel.value(PEAK_BRIGHTNESS);
+            }
+            (Page::Performance, UiEvent::Changed(RENDER_SCALE)) => settings.graphics.render_scale = self.panel.value(RENDER_SCALE),
+            (Page::Performance, UiEvent::Pressed(AUTO_QUALITY)) => settings.graphics.auto_quality = !settings.graphics.auto_quality,
+            (Page::Performance, UiEvent::Changed(TARGET_FPS)) => settings.graphics.target_fps = self.panel.value(TARGET_FPS),
+            (Page::Performance, UiEvent::Changed(MIN_RENDER_SCALE)) => {
+                settings.graphics.min_render_scale = self.panel.value(MIN_RENDER_SCALE);
+            }
+            (Page::Performance, UiEvent::Changed(MEMORY_BUDGET)) => {
+                settings.graphics.memory_budget = (self.panel.value(MEMORY_BUDGET) * BYTES_PER_MIB) as u64;
+            }
+            (Page::Settings | Page::Display | Page::Performance | Page::System, UiEvent::Pressed(_)) => {
+                self.back();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Handles a key press.
+    pub fn key(&mut self, key: KeyCode, settings: MenuSettings) -> Option<MenuAction> {
+        let event = self.panel.key(key);
+        self.handle(event, settings)
+    }
+
+    pub fn cursor_moved(&mut self, x: f32, y: f32, size: winit::dpi::PhysicalSize<u32>, settings: MenuSettings) -> Option<MenuAction> {
+        let event = self.panel.cursor_moved(x, y, size);
+        self.handle(event, settings)
+    }
+
+    pub fn mouse_down(&mut self, size: winit::dpi::PhysicalSize<u32>, settings: MenuSettings) -> Option<MenuAction> {
+        let event = self.panel.mouse_down(size);
+        self.handle(event, settings)
+    }
+
+    pub fn mouse_up(&mut self) {
+        self.panel.mouse_up();
+    }
+
+    /// Scrolls the list under the cursor by `lines` rows, positive being up.
+    pub fn scroll(&mut self, lines: f32, size: winit::dpi::PhysicalSize<u32>) {
+        self.panel.scroll(lines, size);
+    }
+
+    pub fn draw(&self, overlay: &mut Overlay, size: winit::dpi::PhysicalSize<u32>) {
+        let (width, height) = (size.width as f32, size.height as f32);
+        overlay.rect(0.0, 0.0, width, height, [0.0, 0.0, 0.0, 0.35]);
+        self.panel.draw(overlay, size);
+
+        if self.page == Page::Main {
+            // Centered at the bottom of the screen, below the panel
+            let line_height = Overlay::line_height(Self::NOTICE_SCALE);
+            let top = height - (self.notice.len() as f32 + 1.0) * line_height * 1.5;
+            for (i, line) in self.notice.iter().enumerate() {
+                let x = (width - line.chars().count() as f32 * line_height) / 2.0;
+                overlay.text(x.max(0.0), top + i as f32 * line_height * 1.5, line, Self::NOTICE_SCALE, [1.0, 0.8, 0.4, 1.0]);
+            }
+        }
+    }
+}
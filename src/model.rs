@@ -1,24 +1,32 @@
 use std::{io::{BufReader, Cursor}, mem};
 
-use log::warn;
+use tracing::warn;
 use wgpu::util::DeviceExt;
 
-use crate::resources;
+use crate::{collision::Aabb, draw_data::DrawUniform, memory::MemoryUsage, resources};
 
 pub trait Vertex {
     fn desc() -> wgpu::VertexBufferLayout<'static>;
 }
 
+/// Seconds a model takes to fade in after it's loaded.
+const FADE_IN_TIME: f32 = 0.5;
+
+/// Color of loaded meshes, since materials aren't loaded.
+const DEFAULT_COLOR: [f32; 3] = [0.8, 0.8, 0.8];
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ModelVertex {
     pub position: [f32; 3],
     pub color: [f32; 3],
     pub normal: [f32; 3],
+    /// How mirror-like the surface is, from 0 (matte) to 1 (perfect mirror).
+    pub smoothness: f32,
 }
 
 impl ModelVertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x3];
+    const ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x3, 3 => Float32];
 }
 
 impl Vertex for ModelVertex {
@@ -31,33 +39,34 @@ impl Vertex for ModelVertex {
     }
 }
 
-pub struct Model {
+/// A model's geometry on the CPU. It's kept alongside the GPU buffers so
+/// they can be recreated if the device is lost.
+#[derive(Clone)]
+pub struct MeshData {
     pub name: String,
-    pub index_buffer: wgpu::Buffer,
-    pub vertex_buffer: wgpu::Buffer,
-    pub num_indices: u32
+    pub vertices: Vec<ModelVertex>,
+    pub indices: Vec<u32>,
+    pub bounds: Aabb
 }
 
-impl Model {
-    pub async fn load(
-        file_name: &str,
-        device: &wgpu::Device
-    ) -> anyhow::Result<Model> {
+impl MeshData {
+    #[tracing::instrument]
+    pub async fn load(file_name: &str) -> anyhow::Result<MeshData> {
         let obj_text = resources::load_string(file_name).await?;
         let obj_cursor = Cursor::new(obj_text);
         let mut obj_reader = BufReader::new(obj_cursor);
 
-        let (models, _) = tobj::load_obj_buf_async(
+        let (models, _) = tobj::load_obj_buf(
             &mut obj_reader,
             &tobj::LoadOptions {
                 triangulate: true,
                 single_index: true,
                 ..Default::default()
             },
-            |p| async {
+            |_p| {
                 unimplemented!("Materials aren't used")
             },
-        ).await?;
+        )?;
 
         if models.len() > 1 {
             warn!("Found more than one model; only using the first.");
@@ -73,8 +82,9 @@ impl Model {
                             model.mesh.positions[i * 3 + 1],
                             model.mesh.positions[i * 3 + 2],
                         ],
-                        color: [0., 0., 0.],
+                        color: DEFAULT_COLOR,
                         normal: [0., 0., 0.],
+                        smoothness: 0.,
                     }
                 }else{
                     ModelVertex {
@@ -83,36 +93,79 @@ impl Model {
                             model.mesh.positions[i * 3 + 1],
                             model.mesh.positions[i * 3 + 2],
                         ],
-                        color: [0., 0., 0.],
+                        color: DEFAULT_COLOR,
                         normal: [
                             model.mesh.normals[i * 3],
                             model.mesh.normals[i * 3 + 1],
                             model.mesh.normals[i * 3 + 2],
                         ],
+                        smoothness: 0.,
                     }
                 }
             })
             .collect::<Vec<_>>();
 
+        let bounds = Aabb::from_points(vertices.iter().map(|v| v.position.into()))
+            .ok_or_else(|| anyhow::anyhow!("{} has no vertices", file_name))?;
+
+        Ok(MeshData {
+            name: file_name.to_string(),
+            vertices,
+            indices: model.mesh.indices.clone(),
+            bounds
+        })
+    }
+}
+
+pub struct Model {
+    pub mesh: MeshData,
+    pub index_buffer: wgpu::Buffer,
+    pub vertex_buffer: wgpu::Buffer,
+    pub num_indices: u32,
+    /// How far the model has faded in since it was uploaded, from 0 to 1.
+    pub fade: f32,
+}
+
+impl Model {
+    pub fn new(device: &wgpu::Device, mesh: MeshData) -> Model {
         let vertex_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{} Vertex Buffer", file_name)),
-                contents: bytemuck::cast_slice(&vertices),
+                label: Some(&format!("{} Vertex Buffer", mesh.name)),
+                contents: bytemuck::cast_slice(&mesh.vertices),
                 usage: wgpu::BufferUsages::VERTEX,
             }
         );
         let index_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{} Index Buffer", file_name)),
-                contents: bytemuck::cast_slice(&model.mesh.indices),
+                label: Some(&format!("{} Index Buffer", mesh.name)),
+                contents: bytemuck::cast_slice(&mesh.indices),
                 usage: wgpu::BufferUsages::INDEX,
             }
         );
-        Ok(Model {
-            name: file_name.to_string(),
+        Model {
+            num_indices: mesh.indices.len() as u32,
+            mesh,
             index_buffer, vertex_buffer,
-            num_indices: model.mesh.indices.len() as u32
-        })
+            fade: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        self.fade = (self.fade + delta_time / FADE_IN_TIME).min(1.0);
+    }
+
+    pub fn draw_uniform(&self, id: u32) -> DrawUniform {
+        DrawUniform { offset: [0.0; 3], fade: self.fade, id, _padding: [0; 3] }
+    }
+
+    pub fn memory(&self) -> MemoryUsage {
+        let cpu_bytes = mem::size_of_val(self.mesh.vertices.as_slice()) + mem::size_of_val(self.mesh.indices.as_slice());
+        MemoryUsage {
+            vertices: self.mesh.vertices.len() as u64,
+            indices: self.mesh.indices.len() as u64,
+            cpu_bytes: cpu_bytes as u64,
+            ..MemoryUsage::buffer(&self.vertex_buffer) + MemoryUsage::buffer(&self.index_buffer)
+        }
     }
 }
 
@@ -0,0 +1,158 @@
+use std::mem;
+
+use crate::{font, memory::MemoryUsage, model::Vertex};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OverlayVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl OverlayVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+}
+
+impl Vertex for OverlayVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<OverlayVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &OverlayVertex::ATTRIBS
+        }
+    }
+}
+
+/// Screen-space 2D renderer for text and flat rectangles, drawn on top of
+/// everything else. Coordinates are in physical pixels from the top-left.
+pub struct Overlay {
+    vertices: Vec<OverlayVertex>,
+    vertex_buffer: wgpu::Buffer,
+    num_vertices: u32,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Overlay {
+    const INITIAL_CAPACITY: usize = 4096;
+
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/overlayShader.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overlay Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overlay Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    OverlayVertex::desc()
+                ],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None
+        });
+
+        Self {
+            vertices: Vec::new(),
+            vertex_buffer: Self::create_vertex_buffer(device, Self::INITIAL_CAPACITY),
+            num_vertices: 0,
+            pipeline,
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Overlay Vertex Buffer"),
+            size: (capacity * mem::size_of::<OverlayVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+        let quad = [[x, y], [x + width, y], [x + width, y + height], [x, y + height]];
+        for i in [0, 1, 2, 0, 2, 3] {
+            self.vertices.push(OverlayVertex { position: quad[i], color });
+        }
+    }
+
+    /// Draws `text` with its top-left corner at (x, y), each font pixel
+    /// covering `scale` screen pixels.
+    pub fn text(&mut self, x: f32, y: f32, text: &str, scale: f32, color: [f32; 4]) {
+        font::for_each_pixel(text, |px, py| {
+            self.rect(x + px as f32 * scale, y + py as f32 * scale, scale, scale, color);
+        });
+    }
+
+    /// The height of one line of text at the given scale.
+    pub fn line_height(scale: f32) -> f32 {
+        font::GLYPH_SIZE as f32 * scale
+    }
+
+    /// Uploads everything queued since the last frame and clears the queue.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, size: winit::dpi::PhysicalSize<u32>) {
+        // Convert from pixels to normalized device coordinates
+        let (width, height) = (size.width.max(1) as f32, size.height.max(1) as f32);
+        for vertex in &mut self.vertices {
+            vertex.position = [
+                vertex.position[0] / width * 2.0 - 1.0,
+                1.0 - vertex.position[1] / height * 2.0,
+            ];
+        }
+
+        let needed = mem::size_of_val(self.vertices.as_slice()) as wgpu::BufferAddress;
+        if needed > self.vertex_buffer.size() {
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.vertices.len().next_power_of_two());
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.num_vertices = self.vertices.len() as u32;
+        self.vertices.clear();
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        if self.num_vertices == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.num_vertices, 0..1);
+    }
+
+    /// The vertex buffer, plus this frame's vertices on the CPU.
+    pub fn memory(&self) -> MemoryUsage {
+        MemoryUsage {
+            cpu_bytes: (self.vertices.capacity() * mem::size_of::<OverlayVertex>()) as u64,
+            ..MemoryUsage::buffer(&self.vertex_buffer)
+        }
+    }
+}
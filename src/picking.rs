@@ -0,0 +1,101 @@
+//! Finding what's drawn at a pixel from the object IDs in the G-buffer's
+//! color alpha. One texel is copied out each frame and read once the GPU is
+//! done with it, so the answer is a frame or two old but never stalls.
+
+use std::sync::mpsc;
+
+use crate::texture::Texture;
+
+enum Readback {
+    Idle,
+    /// A copy was recorded and still has to be submitted.
+    Copied,
+    /// Submitted and being mapped; the result arrives here.
+    Mapping(mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>),
+}
+
+pub struct ObjectPicker {
+    /// One G-buffer texel.
+    buffer: wgpu::Buffer,
+    readback: Readback,
+    /// The draw index under the pixel at the last readback.
+    picked: Option<usize>,
+}
+
+impl ObjectPicker {
+    /// The size of one `Texture::GBUF_FORMAT` texel.
+    const TEXEL_SIZE: wgpu::BufferAddress = 16;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Object Picking Buffer"),
+            size: Self::TEXEL_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Self { buffer, readback: Readback::Idle, picked: None }
+    }
+
+    /// The index of the draw last seen under the pixel, as given to
+    /// `DrawData::prepare`, or `None` if nothing was drawn there.
+    pub fn picked(&self) -> Option<usize> {
+        self.picked
+    }
+
+    /// Picks up a finished readback.
+    pub fn update(&mut self, device: &wgpu::Device) {
+        let Readback::Mapping(receiver) = &self.readback else { return };
+        if let Err(e) = device.poll(wgpu::PollType::Poll) {
+            tracing::warn!("Failed to poll the GPU for picking: {}", e);
+        }
+        match receiver.try_recv() {
+            Ok(Ok(())) => {
+                let bytes = self.buffer.slice(..).get_mapped_range();
+                // The alpha channel, the last of four floats
+                let id: f32 = bytemuck::pod_read_unaligned(&bytes[12..16]);
+                self.picked = (id as usize).checked_sub(1);
+                drop(bytes);
+                self.buffer.unmap();
+            }
+            Ok(Err(e)) => tracing::warn!("Failed to read the object ID: {}", e),
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {}
+        }
+        self.readback = Readback::Idle;
+    }
+
+    /// Copies the G-buffer `color_texture` at `(x, y)`, unless the last
+    /// copy is still being read.
+    pub fn copy(&mut self, encoder: &mut wgpu::CommandEncoder, color_texture: &Texture, x: u32, y: u32) {
+        if !matches!(self.readback, Readback::Idle) {
+            return;
+        }
+        let size = color_texture.texture.size();
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                origin: wgpu::Origin3d { x: x.min(size.width - 1), y: y.min(size.height - 1), z: 0 },
+                ..color_texture.texture.as_image_copy()
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.buffer,
+                layout: wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: None, rows_per_image: None },
+            },
+            wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        );
+        self.readback = Readback::Copied;
+    }
+
+    /// Starts reading the copy back. Call after submitting the encoder
+    /// given to `copy`.
+    pub fn submitted(&mut self) {
+        if !matches!(self.readback, Readback::Copied) {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            // The picker may be gone by the time this runs
+            let _ = sender.send(result);
+        });
+        self.readback = Readback::Mapping(receiver);
+    }
+}
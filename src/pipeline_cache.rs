@@ -0,0 +1,162 @@
+//! Compiles and caches the variants of a preprocessed shader, keyed by the
+//! defines they were built with. Variants that aren't ready yet are compiled
+//! off the main thread, and the last ready variant is drawn with in the
+//! meantime, so changing a setting never stalls a frame.
+
+use std::{collections::{HashMap, HashSet}, sync::{mpsc, Arc}};
+
+use crate::shader_preprocessor::{preprocess, ShaderDefines};
+
+/// Builds a render pipeline from a compiled variant of the shader.
+pub type BuildPipeline = dyn Fn(&wgpu::Device, &wgpu::ShaderModule) -> wgpu::RenderPipeline + Send + Sync;
+
+/// Preprocesses `source` with `defines` and compiles it. For shaders whose
+/// variant never changes after startup, so they don't need a cache.
+pub fn create_shader_module(
+    device: &wgpu::Device,
+    label: &str,
+    source: &str,
+    defines: &ShaderDefines
+) -> anyhow::Result<wgpu::ShaderModule> {
+    let source = preprocess(source, defines)
+        .map_err(|e| anyhow::anyhow!("Failed to preprocess {} with {}: {}", label, defines, e))?;
+    Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    }))
+}
+
+pub struct PipelineCache {
+    label: &'static str,
+    source: &'static str,
+    device: wgpu::Device,
+    build: Arc<BuildPipeline>,
+    ready: HashMap<ShaderDefines, wgpu::RenderPipeline>,
+    pending: HashMap<ShaderDefines, mpsc::Receiver<anyhow::Result<wgpu::RenderPipeline>>>,
+    /// Variants that failed to compile; they aren't retried.
+    failed: HashSet<ShaderDefines>,
+    /// The variant drawn most recently, used while another one compiles.
+    current: ShaderDefines,
+}
+
+impl PipelineCache {
+    /// Compiles the `initial` variant before returning, so there's always
+    /// something to draw with.
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        source: &'static str,
+        initial: ShaderDefines,
+        build: Arc<BuildPipeline>
+    ) -> anyhow::Result<Self> {
+        let pipeline = Self::compile(device, label, source, &initial, build.as_ref())?;
+        Ok(Self {
+            label,
+            source,
+            device: device.clone(),
+            build,
+            ready: HashMap::from([(initial.clone(), pipeline)]),
+            pending: HashMap::new(),
+            failed: HashSet::new(),
+            current: initial,
+        })
+    }
+
+    fn compile(
+        device: &wgpu::Device,
+        label: &str,
+        source: &str,
+        defines: &ShaderDefines,
+        build: &BuildPipeline
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        let module = create_shader_module(device, label, source, defines)?;
+        // A broken variant goes in `failed`, so it isn't drawn with. This asks
+        // the module rather than using an error scope, since scopes are shared
+        // by the whole device and would catch errors from other threads too;
+        // the error still reaches the uncaptured error handler. On the web the
+        // info can only be awaited, which a synchronous compile can't do
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(error) = pollster::block_on(module.get_compilation_info()).messages.into_iter()
+            .find(|message| message.message_type == wgpu::CompilationMessageType::Error)
+        {
+            anyhow::bail!("{}", error.message);
+        }
+        Ok(build(device, &module))
+    }
+
+    /// The pipeline for `defines` if it's compiled. Otherwise this starts
+    /// compiling it and returns the last variant that was used.
+    pub fn get(&mut self, defines: &ShaderDefines) -> &wgpu::RenderPipeline {
+        self.poll();
+
+        if self.ready.contains_key(defines) {
+            self.current = defines.clone();
+        } else if !self.pending.contains_key(defines) && !self.failed.contains(defines) {
+            self.start_compile(defines.clone());
+            // On the web the compile finished synchronously
+            if self.ready.contains_key(defines) {
+                self.current = defines.clone();
+            }
+        }
+
+        &self.ready[&self.current]
+    }
+
+    /// Moves finished compiles into the ready set.
+    fn poll(&mut self) {
+        let mut finished = Vec::new();
+        for (defines, receiver) in &self.pending {
+            match receiver.try_recv() {
+                Ok(result) => finished.push((defines.clone(), result)),
+                Err(mpsc::TryRecvError::Empty) => {}
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    finished.push((defines.clone(), Err(anyhow::anyhow!("compile thread exited"))));
+                }
+            }
+        }
+        for (defines, result) in finished {
+            self.pending.remove(&defines);
+            self.finish(defines, result);
+        }
+    }
+
+    fn finish(&mut self, defines: ShaderDefines, result: anyhow::Result<wgpu::RenderPipeline>) {
+        match result {
+            Ok(pipeline) => {
+                tracing::debug!("Compiled {} with {}", self.label, defines);
+                self.ready.insert(defines, pipeline);
+            }
+            Err(e) => {
+                tracing::error!("Failed to compile {} with {}: {}", self.label, defines, e);
+                self.failed.insert(defines);
+            }
+        }
+    }
+
+    fn start_compile(&mut self, defines: ShaderDefines) {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                // There are no threads to compile on, so this stalls
+                let result = Self::compile(&self.device, self.label, self.source, &defines, self.build.as_ref());
+                self.finish(defines, result);
+            } else {
+                let (sender, receiver) = mpsc::channel();
+                let device = self.device.clone();
+                let build = self.build.clone();
+                let (label, source) = (self.label, self.source);
+                let thread_defines = defines.clone();
+                let spawned = std::thread::Builder::new()
+                    .name(format!("{} compile", label))
+                    .spawn(move || {
+                        let _ = sender.send(Self::compile(&device, label, source, &thread_defines, build.as_ref()));
+                    });
+                match spawned {
+                    Ok(_) => {
+                        self.pending.insert(defines, receiver);
+                    }
+                    Err(e) => self.finish(defines, Err(e.into())),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Planar reflections. The scene is re-rendered at reduced resolution with
+//! the camera mirrored about the water plane, so sampling the result at a
+//! water pixel's screen position gives its reflection.
+
+use cgmath::{Matrix4, Point3, Vector3};
+
+use crate::{
+    camera::Camera, draw_data::DrawData, memory::MemoryUsage, model::{DrawModel, Model, ModelVertex, Vertex},
+    pipeline_cache::create_shader_module, render_cache::RenderCache, texture::Texture
+};
+
+/// The reflection is rendered at this fraction of the screen's resolution.
+const RESOLUTION_DIVISOR: u32 = 2;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ReflectionUniform {
+    view_proj: [[f32; 4]; 4],
+    camera_position: [f32; 4],
+    /// x is the plane's height; y, z and w are unused
+    plane_height: [f32; 4],
+}
+
+pub struct PlanarReflection {
+    pipeline: wgpu::RenderPipeline,
+    pub texture: Texture,
+    depth_texture: Texture,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl PlanarReflection {
+    pub fn new(
+        device: &wgpu::Device,
+        cache: &RenderCache,
+        config: &wgpu::SurfaceConfiguration,
+        sky_layout: &wgpu::BindGroupLayout,
+        draw_data: &DrawData
+    ) -> anyhow::Result<Self> {
+        let (texture, depth_texture) = Self::create_textures(device, config);
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reflection Uniform Buffer"),
+            size: std::mem::size_of::<ReflectionUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = cache.uniform_layout(wgpu::ShaderStages::VERTEX_FRAGMENT);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("reflection_bind_group"),
+        });
+
+        let shader = create_shader_module(
+            device, "Planar Reflection Shader", include_str!("shaders/planarReflectionShader.wgsl"), &draw_data.defines()
+        )?;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Planar Reflection Pipeline Layout"),
+            bind_group_layouts: &[Some(&bind_group_layout), Some(sky_layout), draw_data.bind_group_layout()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>(),
+            push_constant_ranges: &draw_data.push_constant_ranges(),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Planar Reflection Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    ModelVertex::desc()
+                ],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: Texture::HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                // Mirroring flips the winding of every triangle
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None
+        });
+
+        Ok(Self { pipeline, texture, depth_texture, uniform_buffer, bind_group })
+    }
+
+    fn create_textures(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> (Texture, Texture) {
+        let config = wgpu::SurfaceConfiguration {
+            width: config.width / RESOLUTION_DIVISOR,
+            height: config.height / RESOLUTION_DIVISOR,
+            ..config.clone()
+        };
+        (
+            Texture::create_render_target(device, &config, "reflection_texture", Texture::HDR_FORMAT),
+            Texture::create_render_target(device, &config, "reflection_depth_texture", Texture::DEPTH_FORMAT),
+        )
+    }
+
+    /// Recreates the reflection textures. Bind groups that use `texture`
+    /// need to be recreated after this.
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        (self.texture, self.depth_texture) = Self::create_textures(device, config);
+    }
+
+    pub fn memory(&self) -> MemoryUsage {
+        MemoryUsage::texture(&self.texture.texture)
+            + MemoryUsage::texture(&self.depth_texture.texture)
+            + MemoryUsage::buffer(&self.uniform_buffer)
+    }
+
+    pub fn prepare(&self, queue: &wgpu::Queue, camera: &Camera, plane_height: f32) {
+        // Mirror about the plane y = plane_height
+        let mirror = Matrix4::from_translation(Vector3::new(0.0, plane_height, 0.0))
+            * Matrix4::from_nonuniform_scale(1.0, -1.0, 1.0)
+            * Matrix4::from_translation(Vector3::new(0.0, -plane_height, 0.0));
+        let view_proj = camera.build_view_projection_matrix() * mirror;
+        let camera_position = Point3::from_homogeneous(mirror * camera.position().to_homogeneous());
+
+        let uniform = ReflectionUniform {
+            view_proj: view_proj.into(),
+            camera_position: camera_position.to_homogeneous().into(),
+            plane_height: [plane_height, 0.0, 0.0, 0.0],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    /// Renders `models` into the reflection texture. Model `i` uses draw `i`
+    /// of `draw_data`.
+    pub fn render<'m>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        sky_bind_group: &wgpu::BindGroup,
+        draw_data: &DrawData,
+        models: impl IntoIterator<Item = &'m Model>
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Planar Reflection Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.texture.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(1, sky_bind_group, &[]);
+        for (i, model) in models.into_iter().enumerate() {
+            draw_data.bind(&mut render_pass, 2, i);
+            render_pass.draw_model(model);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Deduplicates wgpu objects that many systems create with identical
+//! descriptors, like single-uniform bind group layouts and linear samplers.
+//! Asking for the same descriptor twice returns the same object.
+
+use std::{cell::RefCell, collections::HashMap};
+
+/// The parts of a sampler descriptor that affect the sampler; labels don't.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct SamplerKey {
+    address_modes: [wgpu::AddressMode; 3],
+    filters: [wgpu::FilterMode; 3],
+    /// The bits of the min and max LOD clamps, since floats aren't hashable
+    lod_clamps: [u32; 2],
+    compare: Option<wgpu::CompareFunction>,
+    anisotropy_clamp: u16,
+    border_color: Option<wgpu::SamplerBorderColor>,
+}
+
+impl SamplerKey {
+    fn new(desc: &wgpu::SamplerDescriptor) -> Self {
+        Self {
+            address_modes: [desc.address_mode_u, desc.address_mode_v, desc.address_mode_w],
+            filters: [desc.mag_filter, desc.min_filter, desc.mipmap_filter],
+            lod_clamps: [desc.lod_min_clamp.to_bits(), desc.lod_max_clamp.to_bits()],
+            compare: desc.compare,
+            anisotropy_clamp: desc.anisotropy_clamp,
+            border_color: desc.border_color,
+        }
+    }
+}
+
+pub struct RenderCache {
+    device: wgpu::Device,
+    layouts: RefCell<HashMap<Vec<wgpu::BindGroupLayoutEntry>, wgpu::BindGroupLayout>>,
+    samplers: RefCell<HashMap<SamplerKey, wgpu::Sampler>>,
+}
+
+impl RenderCache {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            device: device.clone(),
+            layouts: RefCell::new(HashMap::new()),
+            samplers: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// A layout with `entries`. Shared layouts get a generic label, since the
+    /// first system to ask for one isn't the only one using it.
+    pub fn bind_group_layout(&self, entries: &[wgpu::BindGroupLayoutEntry]) -> wgpu::BindGroupLayout {
+        self.layouts
+            .borrow_mut()
+            .entry(entries.to_vec())
+            .or_insert_with(|| self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Shared Bind Group Layout"),
+                entries,
+            }))
+            .clone()
+    }
+
+    /// A layout with one uniform buffer at binding 0, the most common layout
+    /// for a system's settings.
+    pub fn uniform_layout(&self, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayout {
+        self.bind_group_layout(&[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }])
+    }
+
+    /// A sampler matching `desc`. Its label is only used if the sampler is new.
+    pub fn sampler(&self, desc: &wgpu::SamplerDescriptor) -> wgpu::Sampler {
+        self.samplers
+            .borrow_mut()
+            .entry(SamplerKey::new(desc))
+            .or_insert_with(|| self.device.create_sampler(desc))
+            .clone()
+    }
+
+    /// Filters linearly and clamps to the edge; for sampling lower-resolution
+    /// copies of the screen.
+    pub fn linear_clamp_sampler(&self) -> wgpu::Sampler {
+        self.sampler(&wgpu::SamplerDescriptor {
+            label: Some("Linear Clamp Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,155 @@
+//! Drawing the scene at a different resolution from the window. Every pass
+//! up to tonemapping draws at the scaled size, and tonemapping stretches the
+//! result over the screen with bilinear filtering. Debug geometry is still
+//! drawn at full size, so the scene's depth is copied into a screen-sized
+//! depth buffer for it.
+
+use crate::{memory::MemoryUsage, render_cache::RenderCache, texture::Texture};
+
+pub struct RenderScale {
+    /// The scale the scene targets were last created at.
+    scale: f32,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    /// Screen-sized depth, for passes that draw straight to the screen.
+    pub depth_texture: Texture,
+}
+
+impl RenderScale {
+    /// `config` resized by `scale`, kept within the device's largest texture.
+    pub fn scaled_config(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, scale: f32) -> wgpu::SurfaceConfiguration {
+        let max_size = device.limits().max_texture_dimension_2d;
+        let scaled = |size: u32| ((size as f32 * scale).round() as u32).clamp(1, max_size);
+        wgpu::SurfaceConfiguration {
+            width: scaled(config.width),
+            height: scaled(config.height),
+            ..config.clone()
+        }
+    }
+
+    /// `scene_depth` is the depth buffer the scene was drawn with, at `scale`.
+    pub fn new(
+        device: &wgpu::Device,
+        cache: &RenderCache,
+        config: &wgpu::SurfaceConfiguration,
+        scale: f32,
+        scene_depth: &Texture
+    ) -> Self {
+        let bind_group_layout = cache.bind_group_layout(&[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                // Read as a float texture, which GL can load from
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        }]);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, scene_depth);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/depthCopyShader.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth Copy Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Depth Copy Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None
+        });
+
+        Self {
+            scale,
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            depth_texture: Texture::create_gbuf_texture(device, config, "screen_depth_texture", true),
+        }
+    }
+
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, scene_depth: &Texture) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&scene_depth.view),
+                }
+            ],
+            label: Some("depth_copy_bind_group"),
+        })
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Recreates the screen depth after a resize or a scale change.
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, scale: f32, scene_depth: &Texture) {
+        self.scale = scale;
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, scene_depth);
+        self.depth_texture = Texture::create_gbuf_texture(device, config, "screen_depth_texture", true);
+    }
+
+    /// Fills the screen depth from the scene's.
+    pub fn copy_depth(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth Copy Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    pub fn memory(&self) -> MemoryUsage {
+        MemoryUsage::texture(&self.depth_texture.texture)
+    }
+}
@@ -0,0 +1,377 @@
+//! Golden-image tests. Each test renders a fixed scene into an offscreen
+//! texture and compares it with a PNG in tests/golden, so shader and pipeline
+//! changes can't quietly change what's on screen. They need a GPU (a software
+//! one is fine), so they only build with `cargo test --features gpu-tests`.
+//!
+//! A test without a golden image fails. When a new test is added or a change
+//! to the output is intended, rerun with `UPDATE_GOLDEN=1` to write the
+//! images, and check the new ones before committing them.
+//!
+//! The deferred path renders to float textures, which Mesa's software GL
+//! driver only allows with `MESA_EXTENSION_OVERRIDE=+EXT_color_buffer_float`.
+
+use std::path::PathBuf;
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    camera::{Camera, CameraUniform}, collision::Aabb, debug_draw::{self, DebugDraw}, draw_data::DrawData, exposure::Exposure, gpu,
+    lights::Lights, model::{DrawModel, MeshData, Model}, overlay::Overlay, render_cache::RenderCache,
+    settings::{AmbientOcclusion, GraphicsSettings}, sky::{SkyGradient, SkyUniform, TimeOfDay}, texture::Texture,
+    State, DAY_LENGTH, FOG_END, FOG_START
+};
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+/// How far a channel can be from the golden image before the pixel differs.
+/// Drivers don't rasterize and blend identically.
+const CHANNEL_TOLERANCE: u8 = 4;
+/// The share of pixels that may differ, for edge pixels that land
+/// differently on other GPUs.
+const PIXEL_TOLERANCE: f32 = 0.005;
+
+struct Headless {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    adapter: wgpu::Adapter,
+}
+
+impl Headless {
+    /// Any adapter will do, including a software one, since there's no window
+    /// to present to.
+    fn new() -> Self {
+        pollster::block_on(async {
+            let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+                backends: wgpu::Backends::all(),
+                ..Default::default()
+            });
+            let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::None,
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            }).await.expect("No GPU adapter for the render tests");
+            let (device, queue) = gpu::request_device(&adapter).await.expect("Failed to create a device");
+            Self { device, queue, adapter }
+        })
+    }
+
+    /// Stands in for the surface configuration render targets are sized from.
+    fn config(&self) -> wgpu::SurfaceConfiguration {
+        wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: FORMAT,
+            width: WIDTH,
+            height: HEIGHT,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        }
+    }
+
+    /// Records passes with `draw` into a cleared target, then reads it back.
+    fn render(&self, draw: impl FnOnce(&mut wgpu::CommandEncoder, &wgpu::TextureView)) -> image::RgbaImage {
+        let target = Texture::create_render_target(&self.device, &self.config(), "Golden Target", FORMAT);
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Golden Encoder"),
+        });
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Golden Clear Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.1, g: 0.1, b: 0.15, a: 1.0 }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        draw(&mut encoder, &target.view);
+
+        // Copied rows have to be padded to a multiple of 256 bytes
+        let row_bytes = WIDTH * 4;
+        let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Golden Readback Buffer"),
+            size: (padded_row_bytes * HEIGHT) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            target.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(HEIGHT),
+                },
+            },
+            target.texture.size(),
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |result| result.expect("Failed to map the readback buffer"));
+        self.device.poll(wgpu::PollType::Wait).expect("Failed to wait for the GPU");
+        let mapped = readback.slice(..).get_mapped_range();
+        let pixels = mapped
+            .chunks(padded_row_bytes as usize)
+            .flat_map(|row| &row[..row_bytes as usize])
+            .copied()
+            .collect();
+        image::RgbaImage::from_raw(WIDTH, HEIGHT, pixels).expect("Readback has the wrong size")
+    }
+}
+
+/// Fails if `image` differs from tests/golden/`name`.png by more than the
+/// tolerances, or if there's no golden image. With `UPDATE_GOLDEN` set, the
+/// image is written as the new golden instead.
+fn assert_matches_golden(name: &str, image: &image::RgbaImage) {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let path = dir.join(format!("{}.png", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(&dir).expect("Failed to create tests/golden");
+        image.save(&path).expect("Failed to write the golden image");
+        eprintln!("Wrote {}", path.display());
+        return;
+    }
+    if !path.exists() {
+        let actual = std::env::temp_dir().join(format!("{}.actual.png", name));
+        let _ = image.save(&actual);
+        panic!(
+            "{} has no golden image; check the output at {} and rerun with UPDATE_GOLDEN=1 to add it",
+            name, actual.display()
+        );
+    }
+
+    let golden = image::open(&path).expect("Failed to read the golden image").to_rgba8();
+    assert_eq!(golden.dimensions(), image.dimensions(), "{} changed size", name);
+    let differing = golden.pixels()
+        .zip(image.pixels())
+        .filter(|(a, b)| a.0.iter().zip(b.0).any(|(a, b)| a.abs_diff(b) > CHANNEL_TOLERANCE))
+        .count();
+    let share = differing as f32 / (WIDTH * HEIGHT) as f32;
+    if share > PIXEL_TOLERANCE {
+        let actual = std::env::temp_dir().join(format!("{}.actual.png", name));
+        let _ = image.save(&actual);
+        panic!(
+            "{} differs from its golden image in {:.2}% of pixels; the output is at {}",
+            name, share * 100.0, actual.display()
+        );
+    }
+}
+
+#[test]
+fn overlay() {
+    let gpu = Headless::new();
+    let mut overlay = Overlay::new(&gpu.device, FORMAT);
+    overlay.rect(16.0, 16.0, 224.0, 48.0, [0.0, 0.0, 0.0, 0.6]);
+    overlay.text(24.0, 24.0, "Golden", 4.0, [1.0, 0.85, 0.3, 1.0]);
+    overlay.rect(16.0, 96.0, 96.0, 96.0, [1.0, 0.2, 0.2, 1.0]);
+    // Overlaps the red square to check blending
+    overlay.rect(64.0, 144.0, 96.0, 96.0, [0.2, 0.4, 1.0, 0.5]);
+    overlay.prepare(&gpu.device, &gpu.queue, winit::dpi::PhysicalSize::new(WIDTH, HEIGHT));
+
+    let image = gpu.render(|encoder, view| {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        overlay.draw(&mut pass);
+    });
+    assert_matches_golden("overlay", &image);
+}
+
+#[test]
+fn debug_draw() {
+    let gpu = Headless::new();
+    let cache = RenderCache::new(&gpu.device);
+    // The camera's starting position, looking down -z from just above the origin
+    let camera = Camera::new(WIDTH as f32 / HEIGHT as f32, 45.0, 0.1, 100.0);
+    let mut camera_uniform = CameraUniform::new();
+    camera_uniform.update_view_proj(&camera);
+    let camera_buffer = gpu.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Camera Buffer"),
+        contents: bytemuck::cast_slice(&[camera_uniform]),
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+    let camera_layout = cache.uniform_layout(wgpu::ShaderStages::VERTEX_FRAGMENT);
+    let camera_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &camera_layout,
+        entries: &[wgpu::BindGroupEntry { binding: 0, resource: camera_buffer.as_entire_binding() }],
+        label: Some("camera_bind_group"),
+    });
+
+    // The queue is global, so this is the only test that may use it
+    let mut debug = DebugDraw::new(&gpu.device, FORMAT, &camera_layout);
+    let aabb = Aabb::new((-0.5, 0.0, -1.5).into(), (0.5, 1.0, -0.5).into());
+    debug_draw::draw_aabb(&aabb, [0.2, 1.0, 0.2]);
+    debug_draw::draw_sphere((0.0, 0.5, -1.0).into(), 0.4, [1.0, 0.2, 0.2]);
+    debug_draw::draw_text_3d(aabb.max, "box", [1.0, 1.0, 1.0]);
+    debug.prepare(&gpu.device, &gpu.queue, &camera);
+
+    let depth = Texture::create_gbuf_texture(&gpu.device, &gpu.config(), "depth_texture", true);
+    let image = gpu.render(|encoder, view| {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth.view,
+                depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: wgpu::StoreOp::Store }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        debug.draw(&mut pass, &camera_bind_group);
+    });
+    assert_matches_golden("debug_draw", &image);
+}
+
+/// The teapot through the deferred path: G-buffer, lighting and tonemapping,
+/// without the optional passes in between.
+#[test]
+fn deferred_teapot() {
+    let gpu = Headless::new();
+    let device = &gpu.device;
+    let cache = RenderCache::new(device);
+    let config = gpu.config();
+    let graphics = GraphicsSettings::default();
+    // GL needs EXT_color_buffer_float for this; see the module docs
+    let gbuf_features = gpu.adapter.get_texture_format_features(Texture::GBUF_FORMAT);
+    assert!(
+        gbuf_features.allowed_usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT),
+        "{:?} can't render to the G-buffer format", gpu.adapter.get_info().name
+    );
+
+    let camera = Camera::new(WIDTH as f32 / HEIGHT as f32, 45.0, 0.1, 100.0);
+    let mut camera_uniform = CameraUniform::new();
+    camera_uniform.update_view_proj(&camera);
+    let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Camera Buffer"),
+        contents: bytemuck::cast_slice(&[camera_uniform]),
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+    let camera_layout = cache.uniform_layout(wgpu::ShaderStages::VERTEX_FRAGMENT);
+    let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &camera_layout,
+        entries: &[wgpu::BindGroupEntry { binding: 0, resource: camera_buffer.as_entire_binding() }],
+        label: Some("camera_bind_group"),
+    });
+
+    // A fixed time of day, so the sun and sky don't depend on when the test runs
+    let time_of_day = TimeOfDay::new(0.3, DAY_LENGTH);
+    let sky_gradient = pollster::block_on(SkyGradient::load("sky.ron")).expect("Failed to load the sky");
+    let sky_uniform = SkyUniform::new(&time_of_day, &sky_gradient.sample(time_of_day.time), FOG_START, FOG_END);
+    let sky_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Sky Buffer"),
+        contents: bytemuck::cast_slice(&[sky_uniform]),
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+    let sky_layout = cache.uniform_layout(wgpu::ShaderStages::FRAGMENT);
+    let sky_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &sky_layout,
+        entries: &[wgpu::BindGroupEntry { binding: 0, resource: sky_buffer.as_entire_binding() }],
+        label: Some("sky_bind_group"),
+    });
+    let lights = Lights::new(device, &cache);
+    lights.prepare(&gpu.queue, &[]);
+
+    let mesh = pollster::block_on(MeshData::load("teapot.obj")).expect("Failed to load the teapot");
+    let mut model = Model::new(device, mesh);
+    // Skip the fade-in, which would dither the teapot away
+    model.fade = 1.0;
+    let mut draw_data = DrawData::new(device, false);
+    // The teapot is modelled around the origin, where the camera starts, so
+    // move it out in front to see it whole
+    let mut draw = model.draw_uniform(1);
+    draw.offset = [0.0, -2.0, -40.0];
+    draw_data.prepare(device, &gpu.queue, &[draw]);
+
+    let depth_texture = Texture::create_gbuf_texture(device, &config, "depth_texture", true);
+    let normal_texture = Texture::create_gbuf_texture(device, &config, "normal_texture", false);
+    let color_texture = Texture::create_gbuf_texture(device, &config, "color_texture", false);
+    let scene_texture = Texture::create_render_target(device, &config, "scene_texture", Texture::HDR_FORMAT);
+
+    let gbuf_pipeline = State::create_gbuf_pipeline(device, &camera_layout, &draw_data).expect("Failed to build the G-buffer pipeline");
+    let gbuf_layout = State::create_gbuf_bind_group_layout(device);
+    let gbuf_bind_group = State::create_gbuf_bind_group(device, &gbuf_layout, &normal_texture, &color_texture, &depth_texture);
+    let mut lighting_pipelines = State::create_lighting_pipelines(
+        device, &gbuf_layout, &camera_layout, &sky_layout, &lights.bind_group_layout, AmbientOcclusion::Off
+    ).expect("Failed to build the lighting pipeline");
+    let lighting_pipeline = lighting_pipelines.get(&State::lighting_defines(AmbientOcclusion::Off));
+    let mut exposure = Exposure::new(device, &cache, FORMAT, false, &scene_texture).expect("Failed to build the exposure passes");
+    exposure.prepare(&gpu.queue, 0.0, graphics.min_exposure, &graphics);
+
+    let image = gpu.render(|encoder, view| {
+        let mut gbuf_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("G-Buffer Pass"),
+            color_attachments: &[&normal_texture, &color_texture].map(|texture| Some(wgpu::RenderPassColorAttachment {
+                view: &texture.view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), store: wgpu::StoreOp::Store },
+            })),
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth_texture.view,
+                depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: wgpu::StoreOp::Store }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        gbuf_pass.set_pipeline(&gbuf_pipeline);
+        gbuf_pass.set_bind_group(0, &camera_bind_group, &[]);
+        draw_data.bind(&mut gbuf_pass, 1, 0);
+        gbuf_pass.draw_model(&model);
+        drop(gbuf_pass);
+
+        let mut lighting_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Lighting Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &scene_texture.view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        lighting_pass.set_pipeline(lighting_pipeline);
+        lighting_pass.set_bind_group(0, &gbuf_bind_group, &[]);
+        lighting_pass.set_bind_group(1, &camera_bind_group, &[]);
+        lighting_pass.set_bind_group(2, &sky_bind_group, &[]);
+        lighting_pass.set_bind_group(3, &lights.bind_group, &[]);
+        lighting_pass.draw(0..3, 0..1);
+        drop(lighting_pass);
+
+        exposure.measure(encoder);
+        let mut tonemap_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tonemap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        exposure.draw(&mut tonemap_pass);
+    });
+    assert_matches_golden("deferred_teapot", &image);
+}
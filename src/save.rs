@@ -0,0 +1,95 @@
+//! The player's save file: progress that should outlast the game closing.
+//! Only native builds save; on the web every session starts fresh.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{stats::Stats, status_effects::ActiveEffect};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerSave {
+    /// Ids of unlocked achievements.
+    pub achievements: Vec<String>,
+    pub stats: Stats,
+    /// Ids of tutorial hints the player has already followed.
+    pub completed_hints: Vec<String>,
+    pub status_effects: Vec<ActiveEffect>,
+    /// The file on disk couldn't be read or moved aside, so writing would
+    /// destroy it.
+    #[serde(skip)]
+    protected: bool,
+}
+
+impl PlayerSave {
+    #[cfg(not(target_arch = "wasm32"))]
+    const PATH: &'static str = "saves/player.ron";
+
+    /// Reads the save, or starts a new one if there isn't one. A save that
+    /// can't be parsed is renamed to `player.ron.corrupt-<seconds>` so it can
+    /// be recovered by hand. One that can't even be read or moved is left
+    /// alone, and the new save is never written over it.
+    pub fn load() -> Self {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                Self::default()
+            } else {
+                let text = match std::fs::read_to_string(Self::PATH) {
+                    Ok(text) => text,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+                    Err(e) => {
+                        tracing::error!("Failed to read {}; it won't be overwritten: {}", Self::PATH, e);
+                        return Self { protected: true, ..Self::default() };
+                    }
+                };
+                ron::from_str(&text).unwrap_or_else(|e| {
+                    tracing::error!("Failed to parse {}: {}", Self::PATH, e);
+                    Self::move_aside()
+                })
+            }
+        }
+    }
+
+    /// Renames an unparseable save out of the way of the new one.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn move_aside() -> Self {
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let moved = format!("{}.corrupt-{}", Self::PATH, seconds);
+        match std::fs::rename(Self::PATH, &moved) {
+            Ok(()) => {
+                tracing::warn!("Moved the unreadable save to {}; starting a new one", moved);
+                Self::default()
+            }
+            Err(e) => {
+                tracing::error!("Failed to move {} aside; it won't be overwritten: {}", Self::PATH, e);
+                Self { protected: true, ..Self::default() }
+            }
+        }
+    }
+
+    pub fn write(&self) -> anyhow::Result<()> {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                Ok(())
+            } else {
+                if self.protected {
+                    // Already reported by `load`
+                    tracing::debug!("Not saving over {}, which couldn't be read", Self::PATH);
+                    return Ok(());
+                }
+                let path = std::path::Path::new(Self::PATH);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+                // Write to a temporary file first, so a crash mid-write can't corrupt the save
+                let temporary = path.with_extension("ron.tmp");
+                std::fs::write(&temporary, text)?;
+                std::fs::rename(&temporary, path)?;
+                Ok(())
+            }
+        }
+    }
+}
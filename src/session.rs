@@ -0,0 +1,57 @@
+//! Notices when the last run crashed. A marker file exists while the game
+//! runs and is removed on a clean exit, so finding one at launch means the
+//! previous run never got that far. If the panic hook wrote a crash report,
+//! its path is kept next to the marker so the next launch can point to it.
+
+use std::path::PathBuf;
+
+#[cfg(not(target_arch = "wasm32"))]
+const MARKER: &str = "saves/running";
+/// Holds the path of the crash report from the run that left the marker.
+#[cfg(not(target_arch = "wasm32"))]
+const REPORT: &str = "saves/last-crash-report";
+
+/// A previous run that didn't exit cleanly.
+pub struct Crash {
+    /// The crash report, if it panicked. A run that hung or was killed
+    /// leaves no report.
+    pub report: Option<PathBuf>,
+}
+
+/// Marks the game as running. Returns the previous run if it crashed.
+/// The web has nowhere to keep the marker, so it never reports a crash.
+pub fn begin() -> Option<Crash> {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            None
+        } else {
+            let path = std::path::Path::new(MARKER);
+            let crashed = path.exists();
+            // A report is only for the run that wrote it, so it's read at most once
+            let report = std::fs::read_to_string(REPORT).ok().map(PathBuf::from);
+            let _ = std::fs::remove_file(REPORT);
+            let written = path.parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(path, std::process::id().to_string()));
+            if let Err(e) = written {
+                tracing::warn!("Failed to write {}; crashes won't be noticed: {}", MARKER, e);
+            }
+            crashed.then_some(Crash { report })
+        }
+    }
+}
+
+/// Remembers where this run's crash report went, for the next launch.
+/// Called from the panic hook, so it only reports failure.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn record_report(report: &std::path::Path) -> std::io::Result<()> {
+    std::fs::write(REPORT, report.to_string_lossy().as_bytes())
+}
+
+/// Marks a clean exit.
+pub fn end() {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Err(e) = std::fs::remove_file(MARKER) {
+        tracing::warn!("Failed to remove {}: {}", MARKER, e);
+    }
+}
@@ -0,0 +1,131 @@
+/// How reflective surfaces get their reflections.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReflectionMode {
+    Off,
+    SsrLow,
+    SsrHigh,
+    /// Re-renders the scene mirrored about the water plane. Other reflective
+    /// surfaces fall back to low-quality SSR.
+    Planar,
+}
+
+impl ReflectionMode {
+    pub fn next(self) -> Self {
+        match self {
+            ReflectionMode::Off => ReflectionMode::SsrLow,
+            ReflectionMode::SsrLow => ReflectionMode::SsrHigh,
+            ReflectionMode::SsrHigh => ReflectionMode::Planar,
+            ReflectionMode::Planar => ReflectionMode::Off,
+        }
+    }
+
+    /// Ray-march and refinement steps for screen-space reflections.
+    pub fn ssr_steps(self) -> (u32, u32) {
+        match self {
+            ReflectionMode::Off => (0, 0),
+            ReflectionMode::SsrLow | ReflectionMode::Planar => (16, 2),
+            ReflectionMode::SsrHigh => (64, 6),
+        }
+    }
+}
+
+/// How much ambient light is blocked by nearby geometry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AmbientOcclusion {
+    Off,
+    /// Screen-space ambient occlusion, estimated from the depth buffer.
+    Ssao,
+    /// SSAO that also finds the least blocked direction, so ambient light
+    /// is brighter on surfaces open to the sky.
+    BentNormals,
+}
+
+impl AmbientOcclusion {
+    pub fn next(self) -> Self {
+        match self {
+            AmbientOcclusion::Off => AmbientOcclusion::Ssao,
+            AmbientOcclusion::Ssao => AmbientOcclusion::BentNormals,
+            AmbientOcclusion::BentNormals => AmbientOcclusion::Off,
+        }
+    }
+}
+
+/// Options that trade quality for performance.
+#[derive(Copy, Clone, Debug)]
+pub struct GraphicsSettings {
+    pub reflections: ReflectionMode,
+    pub godrays: bool,
+    pub ambient_occlusion: AmbientOcclusion,
+    /// The resolution the scene is drawn at, relative to the window. Below 1
+    /// is faster and blurrier; above 1 supersamples.
+    pub render_scale: f32,
+    /// Lower the render scale when the frame rate drops below
+    /// `target_fps`, down to `min_render_scale`. See `AutoQuality`.
+    pub auto_quality: bool,
+    pub target_fps: f32,
+    pub min_render_scale: f32,
+    /// Auto exposure never goes below this, so dark caves stay dark.
+    pub min_exposure: f32,
+    /// Auto exposure never goes above this, so bright scenes aren't washed out.
+    pub max_exposure: f32,
+    /// Applied after tonemapping. Above 1 brightens dark areas, for displays
+    /// where caves are too dark to see in.
+    pub gamma: f32,
+    /// With HDR output, the brightness in nits of what SDR shows as white.
+    pub paper_white: f32,
+    /// With HDR output, the brightest the display can show, in nits.
+    /// Highlights roll off towards it.
+    pub peak_brightness: f32,
+    /// Memory, in bytes, the game aims to stay under. Going over is logged
+    /// and shown in the memory overlay.
+    pub memory_budget: u64,
+    /// Draw with the minimal forward renderer instead of the full one.
+    pub forward: bool,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            reflections: ReflectionMode::SsrHigh,
+            godrays: true,
+            ambient_occlusion: AmbientOcclusion::Ssao,
+            render_scale: 1.0,
+            auto_quality: false,
+            target_fps: 60.0,
+            min_render_scale: 0.5,
+            min_exposure: 0.3,
+            max_exposure: 4.0,
+            gamma: 1.0,
+            paper_white: 200.0,
+            peak_brightness: 1000.0,
+            memory_budget: 1024 * 1024 * 1024,
+            forward: false,
+        }
+    }
+}
+
+impl GraphicsSettings {
+    /// The forward renderer with every optional effect off, for safe mode.
+    pub fn safe() -> Self {
+        Self {
+            reflections: ReflectionMode::Off,
+            godrays: false,
+            ambient_occlusion: AmbientOcclusion::Off,
+            forward: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// Options that change how the game plays rather than how it looks.
+#[derive(Copy, Clone, Debug)]
+pub struct GameplaySettings {
+    /// Whether tutorial hints are shown.
+    pub hints: bool,
+}
+
+impl Default for GameplaySettings {
+    fn default() -> Self {
+        Self { hints: true }
+    }
+}
@@ -0,0 +1,169 @@
+//! A small preprocessor that lets one WGSL file describe several shader
+//! variants. Lines between `#ifdef NAME` (or `#ifndef NAME`), an optional
+//! `#else` and `#endif` are kept or dropped depending on which names are
+//! defined. Dropped lines become blank so error line numbers still match the
+//! original file.
+
+use std::collections::BTreeSet;
+
+use anyhow::bail;
+
+/// The feature names a shader variant is compiled with.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ShaderDefines(BTreeSet<&'static str>);
+
+impl ShaderDefines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `name` if `enabled`.
+    pub fn set(mut self, name: &'static str, enabled: bool) -> Self {
+        if enabled {
+            self.0.insert(name);
+        }
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains(name)
+    }
+}
+
+impl std::fmt::Display for ShaderDefines {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "(no defines)");
+        }
+        write!(f, "{}", self.0.iter().copied().collect::<Vec<_>>().join(", "))
+    }
+}
+
+struct Block {
+    /// Whether lines in the current branch are kept.
+    active: bool,
+    /// Whether the enclosing block is kept; an inactive parent hides both branches.
+    parent_active: bool,
+    seen_else: bool,
+    line: usize,
+}
+
+pub fn preprocess(source: &str, defines: &ShaderDefines) -> anyhow::Result<String> {
+    let mut output = String::with_capacity(source.len());
+    let mut blocks: Vec<Block> = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let active = blocks.last().is_none_or(|block| block.active);
+        let trimmed = line.trim();
+
+        if let Some(directive) = trimmed.strip_prefix('#') {
+            let mut parts = directive.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some(kind @ ("ifdef" | "ifndef")), Some(name)) => {
+                    let defined = defines.contains(name);
+                    blocks.push(Block {
+                        active: active && (defined == (kind == "ifdef")),
+                        parent_active: active,
+                        seen_else: false,
+                        line: line_number,
+                    });
+                }
+                (Some("else"), None) => {
+                    let Some(block) = blocks.last_mut() else {
+                        bail!("line {}: #else without #ifdef", line_number);
+                    };
+                    if block.seen_else {
+                        bail!("line {}: second #else for the #ifdef on line {}", line_number, block.line);
+                    }
+                    block.seen_else = true;
+                    block.active = block.parent_active && !block.active;
+                }
+                (Some("endif"), None) => {
+                    if blocks.pop().is_none() {
+                        bail!("line {}: #endif without #ifdef", line_number);
+                    }
+                }
+                _ => bail!("line {}: unknown directive {:?}", line_number, trimmed),
+            }
+            output.push('\n');
+            continue;
+        }
+
+        if active {
+            output.push_str(line);
+        }
+        output.push('\n');
+    }
+
+    if let Some(block) = blocks.last() {
+        bail!("line {}: #ifdef is never closed with #endif", block.line);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defines(names: &[&'static str]) -> ShaderDefines {
+        names.iter().fold(ShaderDefines::new(), |defines, name| defines.set(name, true))
+    }
+
+    /// The kept lines, without the blanks left for dropped ones.
+    fn kept(source: &str, names: &[&'static str]) -> Vec<String> {
+        let output = preprocess(source, &defines(names)).expect("Preprocessing failed");
+        output.lines().filter(|line| !line.is_empty()).map(str::to_string).collect()
+    }
+
+    fn error(source: &str) -> String {
+        preprocess(source, &ShaderDefines::new()).expect_err("Preprocessing should fail").to_string()
+    }
+
+    #[test]
+    fn ifdef_and_ifndef() {
+        let source = "a\n#ifdef X\nb\n#endif\n#ifndef X\nc\n#endif\nd";
+        assert_eq!(kept(source, &[]), ["a", "c", "d"]);
+        assert_eq!(kept(source, &["X"]), ["a", "b", "d"]);
+    }
+
+    #[test]
+    fn else_branch() {
+        let source = "#ifdef X\na\n#else\nb\n#endif";
+        assert_eq!(kept(source, &[]), ["b"]);
+        assert_eq!(kept(source, &["X"]), ["a"]);
+    }
+
+    #[test]
+    fn nesting() {
+        let source = "#ifdef X\na\n#ifdef Y\nb\n#else\nc\n#endif\nd\n#else\ne\n#ifdef Y\nf\n#endif\n#endif";
+        assert_eq!(kept(source, &[]), ["e"]);
+        assert_eq!(kept(source, &["X"]), ["a", "c", "d"]);
+        assert_eq!(kept(source, &["Y"]), ["e", "f"]);
+        assert_eq!(kept(source, &["X", "Y"]), ["a", "b", "d"]);
+    }
+
+    #[test]
+    fn inactive_parent_hides_else() {
+        // The #else of a block inside a dropped one mustn't turn lines back on
+        let source = "#ifdef X\n#ifdef Y\na\n#else\nb\n#endif\n#endif";
+        assert!(kept(source, &[]).is_empty());
+    }
+
+    #[test]
+    fn line_numbers_are_kept() {
+        let source = "a\n#ifdef X\nb\n#endif\nc";
+        assert_eq!(preprocess(source, &ShaderDefines::new()).unwrap(), "a\n\n\n\nc\n");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("a\n#endif"), "line 2: #endif without #ifdef");
+        assert_eq!(error("#else"), "line 1: #else without #ifdef");
+        assert_eq!(error("#ifdef X\n#else\n#else\n#endif"), "line 3: second #else for the #ifdef on line 1");
+        assert_eq!(error("a\n#ifdef X\nb"), "line 2: #ifdef is never closed with #endif");
+        assert_eq!(error("#ifdef X\n#ifdef Y\n#endif"), "line 1: #ifdef is never closed with #endif");
+        assert_eq!(error("#define X"), "line 1: unknown directive \"#define X\"");
+        assert_eq!(error("#ifdef"), "line 1: unknown directive \"#ifdef\"");
+    }
+}
@@ -0,0 +1,28 @@
+// The scene's depth, at the render scale
+@group(0) @binding(0)
+var depthTexture: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) uv: vec2f,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) id: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    var uv = vec2<f32>(f32((id << 1) & 2), f32(id & 2));
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2, -2) + vec2<f32>(-1, 1), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Copies the nearest scene depth into a depth buffer of another size.
+// Depth can't be filtered meaningfully, so there's no blending between texels.
+@fragment
+fn fs_main(in: VertexOutput) -> @builtin(frag_depth) f32 {
+    let size = vec2<f32>(textureDimensions(depthTexture));
+    let coords = vec2<i32>(min(in.uv * size, size - 1.0));
+    return textureLoad(depthTexture, coords, 0).r;
+}
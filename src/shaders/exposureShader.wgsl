@@ -0,0 +1,102 @@
+// The texture being read: the HDR scene, a luminance mip, or the average
+@group(0) @binding(0)
+var sourceTexture: texture_2d<f32>;
+@group(0) @binding(1)
+var sourceSampler: sampler;
+
+struct ExposureSettings {
+    key: f32, // The brightness an average scene is exposed to
+    min_exposure: f32,
+    max_exposure: f32,
+    gamma: f32, // Applied after tonemapping; above 1 brightens dark areas
+    paper_white: f32, // HDR output only, in nits
+    peak_brightness: f32, // HDR output only, in nits
+};
+// Average log2 luminance the eye has adapted to so far
+@group(1) @binding(0)
+var adaptedTexture: texture_2d<f32>;
+@group(1) @binding(1)
+var<uniform> settings: ExposureSettings;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) uv: vec2f,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) id: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    var uv = vec2<f32>(f32((id << 1) & 2), f32(id & 2));
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2, -2) + vec2<f32>(-1, 1), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn luminance(color: vec3f) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Scene to log2 luminance, at the resolution of the first luminance mip.
+@fragment
+fn fs_luminance(in: VertexOutput) -> @location(0) vec4f {
+    let color = textureSampleLevel(sourceTexture, sourceSampler, in.uv, 0.0).rgb;
+    return vec4<f32>(log2(max(luminance(color), 0.0001)), 0.0, 0.0, 1.0);
+}
+
+// Averages 2x2 texels of the previous mip, so the last mip is the average of
+// the whole screen.
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4f {
+    let coords = vec2<i32>(in.clip_position.xy) * 2;
+    let sum = textureLoad(sourceTexture, coords, 0).r
+        + textureLoad(sourceTexture, coords + vec2<i32>(1, 0), 0).r
+        + textureLoad(sourceTexture, coords + vec2<i32>(0, 1), 0).r
+        + textureLoad(sourceTexture, coords + vec2<i32>(1, 1), 0).r;
+    return vec4<f32>(sum * 0.25, 0.0, 0.0, 1.0);
+}
+
+// Outputs this frame's average; the pipeline blends it into the adapted value
+// so exposure changes gradually.
+@fragment
+fn fs_adapt(in: VertexOutput) -> @location(0) vec4f {
+    return vec4<f32>(textureLoad(sourceTexture, vec2<i32>(0, 0), 0).r, 0.0, 0.0, 1.0);
+}
+
+// Narkowicz's fit of the ACES filmic curve.
+fn aces(color: vec3f) -> vec3f {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return saturate((color * (a * color + b)) / (color * (c * color + d) + e));
+}
+
+#ifdef HDR_OUTPUT
+// Maps exposed scene color to scRGB, where 1 is 80 nits. Up to 1 lands at
+// paper white, and brighter highlights roll off smoothly towards the peak.
+fn hdr_shoulder(color: vec3f) -> vec3f {
+    let white = settings.paper_white / 80.0;
+    let headroom = max(settings.peak_brightness / 80.0 - white, 0.01);
+    let scaled = color * white;
+    let over = max(scaled - white, vec3<f32>(0.0));
+    return min(scaled, vec3<f32>(white)) + headroom * (1.0 - exp(-over / headroom));
+}
+#endif
+
+@fragment
+fn fs_tonemap(in: VertexOutput) -> @location(0) vec4f {
+    // Bilinear, since the scene may be drawn at a different resolution
+    let color = textureSampleLevel(sourceTexture, sourceSampler, in.uv, 0.0).rgb;
+    let average = exp2(textureLoad(adaptedTexture, vec2<i32>(0, 0), 0).r);
+    let exposure = clamp(settings.key / average, settings.min_exposure, settings.max_exposure);
+#ifdef HDR_OUTPUT
+    let mapped = hdr_shoulder(pow(color * exposure, vec3<f32>(1.0 / settings.gamma)));
+#else
+    let mapped = pow(aces(color * exposure), vec3<f32>(1.0 / settings.gamma));
+#endif
+    // Still linear; the sRGB or scRGB surface encodes it
+    return vec4<f32>(mapped, 1.0);
+}
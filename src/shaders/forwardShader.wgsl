@@ -0,0 +1,74 @@
+struct CameraUniform {
+    view_proj: mat4x4f,
+    inv_view_proj: mat4x4f,
+    position: vec4f,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct SkyUniform {
+    sun_direction: vec4f,
+    sun_color: vec4f,
+    ambient_color: vec4f,
+    fog_color: vec4f,
+    zenith_color: vec4f,
+    fog_range: vec4f, // x: start, y: end
+};
+@group(1) @binding(0)
+var<uniform> sky: SkyUniform;
+
+// Preprocessed before compiling; see draw_data.rs
+struct DrawUniform {
+    offset: vec3f,
+    fade: f32,
+    id: u32, // The object ID
+};
+#ifdef PUSH_CONSTANTS
+var<push_constant> draw: DrawUniform;
+#else
+@group(2) @binding(0)
+var<uniform> draw: DrawUniform;
+#endif
+
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(1) color: vec3f,
+    @location(2) normal: vec3f,
+    @location(3) smoothness: f32
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) color: vec3f,
+    @location(1) normal: vec3f,
+    @location(2) world_position: vec3f
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let world_position = model.position + draw.offset;
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 1.0);
+    out.color = model.color;
+    out.normal = model.normal;
+    out.world_position = world_position;
+    return out;
+}
+
+// Sun, ambient and fog only, written straight to the screen. Fading meshes
+// pop in halfway instead of dithering.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    if draw.fade < 0.5 {
+        discard;
+    }
+
+    let normal = normalize(in.normal);
+    let diffuse = max(dot(normal, sky.sun_direction.xyz), 0.0);
+    let lit = in.color * (sky.ambient_color.rgb + sky.sun_color.rgb * diffuse);
+
+    let fog = smoothstep(sky.fog_range.x, sky.fog_range.y, distance(in.world_position, camera.position.xyz));
+    return vec4<f32>(mix(lit, sky.fog_color.rgb, fog), 1.0);
+}
@@ -1,19 +1,36 @@
 struct CameraUniform {
     view_proj: mat4x4f,
+    inv_view_proj: mat4x4f,
+    position: vec4f,
 };
 @group(0) @binding(0) 
 var<uniform> camera: CameraUniform;
 
+// Preprocessed before compiling; see draw_data.rs
+struct DrawUniform {
+    offset: vec3f,
+    fade: f32,
+    id: u32, // The object ID
+};
+#ifdef PUSH_CONSTANTS
+var<push_constant> draw: DrawUniform;
+#else
+@group(1) @binding(0)
+var<uniform> draw: DrawUniform;
+#endif
+
 struct VertexInput {
     @location(0) position: vec3f,
     @location(1) color: vec3f,
-    @location(2) normal: vec3f
+    @location(2) normal: vec3f,
+    @location(3) smoothness: f32
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4f,
     @location(0) color: vec3f,
-    @location(1) normal: vec3f // world-space normal
+    @location(1) normal: vec3f, // world-space normal
+    @location(2) smoothness: f32
 }
 
 @vertex
@@ -21,22 +38,33 @@ fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.color = model.color + model.position;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    out.color = model.color;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position + draw.offset, 1.0);
     out.normal = model.normal;
+    out.smoothness = model.smoothness;
     return out;
 }
 
 struct GBufferOutput {
-  @location(0) normal: vec4f, // a: smoothness?
-  @location(1) color: vec4f // a: emissive?
+  @location(0) normal: vec4f, // a: smoothness
+  @location(1) color: vec4f // a: object ID, exact as a float up to 2^24
+}
+
+// A 4x4 ordered dither, so fading meshes dissolve without blending.
+fn dither_threshold(pixel: vec2<u32>) -> f32 {
+    var bayer = array<f32, 16>(0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+    return (bayer[(pixel.y % 4u) * 4u + pixel.x % 4u] + 0.5) / 16.0;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> GBufferOutput {
+    if draw.fade < dither_threshold(vec2<u32>(in.clip_position.xy)) {
+        discard;
+    }
+
     var output: GBufferOutput;
-    output.normal = vec4(normalize(in.normal), 1.0);
-    output.color = vec4(in.color, 1.0);
+    output.normal = vec4(normalize(in.normal), in.smoothness);
+    output.color = vec4(in.color, f32(draw.id));
 
     return output;
 }
\ No newline at end of file
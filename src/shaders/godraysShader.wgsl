@@ -0,0 +1,88 @@
+@group(0) @binding(0)
+var normalTexture: texture_2d<f32>;
+@group(0) @binding(1)
+var colorTexture: texture_2d<f32>;
+@group(0) @binding(2)
+var depthTexture: texture_2d<f32>;
+
+struct CameraUniform {
+    view_proj: mat4x4f,
+    inv_view_proj: mat4x4f,
+    position: vec4f,
+};
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct SkyUniform {
+    sun_direction: vec4f,
+    sun_color: vec4f,
+    ambient_color: vec4f,
+    fog_color: vec4f,
+    zenith_color: vec4f,
+    fog_range: vec4f, // x: start, y: end
+};
+@group(2) @binding(0)
+var<uniform> sky: SkyUniform;
+
+struct GodraysUniform {
+    intensity: f32, // 0 disables the effect
+    samples: u32,
+    decay: f32, // How much each sample further from the pixel counts
+    reach: f32, // Fraction of the way to the sun that's sampled
+};
+@group(3) @binding(0)
+var<uniform> godrays: GodraysUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) uv: vec2f,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) id: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    var uv = vec2<f32>(f32((id << 1) & 2), f32(id & 2));
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2, -2) + vec2<f32>(-1, 1), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Blurs the visible sky towards the sun's position on screen, so gaps in
+// geometry that let sunlight through streak outwards.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    // The sun is so far away that only its direction matters
+    let sun_clip = camera.view_proj * vec4<f32>(sky.sun_direction.xyz, 0.0);
+    if godrays.intensity <= 0.0 || sun_clip.w <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+    let sun_uv = vec2<f32>(sun_clip.x / sun_clip.w * 0.5 + 0.5, 0.5 - sun_clip.y / sun_clip.w * 0.5);
+
+    let size = vec2<f32>(textureDimensions(depthTexture));
+    let stride = (sun_uv - in.uv) * godrays.reach / f32(godrays.samples);
+    var uv = in.uv;
+    var weight = 1.0;
+    var light = 0.0;
+    for (var i = 0u; i < godrays.samples; i++) {
+        uv += stride;
+        if any(uv < vec2<f32>(0.0)) || any(uv >= vec2<f32>(1.0)) {
+            break;
+        }
+        // Only the sky lets sunlight through
+        if textureLoad(depthTexture, vec2<i32>(uv * size), 0).r >= 1.0 {
+            light += weight;
+        }
+        weight *= godrays.decay;
+    }
+    light /= f32(godrays.samples);
+
+    // Fade out as the sun leaves the screen and as it nears the horizon
+    let off_screen = max(max(-sun_uv.x, sun_uv.x - 1.0), max(-sun_uv.y, sun_uv.y - 1.0));
+    let screen_fade = 1.0 - smoothstep(0.0, 0.5, off_screen);
+    let height_fade = smoothstep(-0.05, 0.1, sky.sun_direction.y);
+
+    let strength = light * godrays.intensity * screen_fade * height_fade;
+    return vec4<f32>(sky.sun_color.rgb * strength, 0.0);
+}
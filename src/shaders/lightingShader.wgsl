@@ -1,15 +1,45 @@
 @group(0) @binding(0)
-var normalSampler: sampler;
-@group(0) @binding(1)
 var normalTexture: texture_2d<f32>;
-
-@group(0) @binding(2)
-var colorSampler: sampler;
-@group(0) @binding(3)
+@group(0) @binding(1)
 var colorTexture: texture_2d<f32>;
+// Depth is bound as a float texture: GLSL can't load from depth textures
+@group(0) @binding(2)
+var depthTexture: texture_2d<f32>;
+
+struct CameraUniform {
+    view_proj: mat4x4f,
+    inv_view_proj: mat4x4f,
+    position: vec4f,
+};
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct SkyUniform {
+    sun_direction: vec4f,
+    sun_color: vec4f,
+    ambient_color: vec4f,
+    fog_color: vec4f,
+    zenith_color: vec4f,
+    fog_range: vec4f, // x: start, y: end
+};
+@group(2) @binding(0)
+var<uniform> sky: SkyUniform;
+
+const MAX_LIGHTS = 8;
+struct PointLight {
+    position: vec4f, // w: radius
+    color: vec4f,
+};
+struct LightsUniform {
+    count: u32,
+    lights: array<PointLight, MAX_LIGHTS>,
+};
+@group(3) @binding(0)
+var<uniform> lights: LightsUniform;
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4f,
+    @location(0) uv: vec2f,
 };
 
 @vertex
@@ -19,21 +49,135 @@ fn vs_main(
     var out: VertexOutput;
 	var uv = vec2<f32>(f32((id << 1) & 2), f32(id & 2));
     out.clip_position = vec4<f32>(uv * vec2<f32>(2, -2) + vec2<f32>(-1, 1), 0.0, 1.0);
+    out.uv = uv;
     return out;
 }
 
 struct GBufferOutput {
-  @location(0) normal: vec4f, // a: smoothness?
-  @location(1) color: vec4f // a: emissive?
+  @location(0) normal: vec4f, // a: smoothness
+  @location(1) color: vec4f // a: object ID, see gBufferShader
+}
+
+// Reconstructs the world-space position of a pixel from its depth.
+fn world_position(uv: vec2f, depth: f32) -> vec3f {
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = camera.inv_view_proj * ndc;
+    return world.xyz / world.w;
+}
+
+// Diffuse light from every point light reaching a surface.
+fn point_lighting(position: vec3f, normal: vec3f) -> vec3f {
+    var total = vec3<f32>(0.0);
+    for (var i = 0u; i < min(lights.count, u32(MAX_LIGHTS)); i++) {
+        let light = lights.lights[i];
+        let to_light = light.position.xyz - position;
+        let light_distance = length(to_light);
+        let radius = light.position.w;
+
+        // Inverse square falloff, windowed to reach zero at the radius
+        let window = saturate(1.0 - pow(light_distance / radius, 4.0));
+        let attenuation = window * window / (light_distance * light_distance + 1.0);
+        let diffuse = max(dot(normal, to_light / max(light_distance, 0.0001)), 0.0);
+        total += light.color.rgb * diffuse * attenuation;
+    }
+    return total;
+}
+
+#ifdef SSAO
+const AO_SAMPLES = 12u;
+// How far around a surface, in world units, geometry can block ambient light
+const AO_RADIUS = 0.6;
+
+// A cheap per-pixel random number in [0, 1), used to rotate the sample
+// pattern so banding becomes noise.
+fn hash(coords: vec2f) -> f32 {
+    return fract(sin(dot(coords, vec2<f32>(12.9898, 78.233))) * 43758.5453);
+}
+
+// How much of the hemisphere above a surface is open, in w, and the average
+// open direction, in xyz. Points around the surface are projected onto the
+// screen and count as blocked if the depth buffer has nearby geometry in
+// front of them.
+fn ambient_occlusion(coords: vec2<i32>, position: vec3f, normal: vec3f) -> vec4f {
+    let size = vec2<f32>(textureDimensions(depthTexture));
+    let up = select(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), abs(normal.y) > 0.99);
+    let tangent = normalize(cross(normal, up));
+    let bitangent = cross(normal, tangent);
+    let rotation = hash(vec2<f32>(coords)) * 6.2831853;
+
+    var open = 0.0;
+    var bent = vec3<f32>(0.0);
+    for (var i = 0u; i < AO_SAMPLES; i++) {
+        // Cosine-weighted directions spiralling around the normal by the golden angle
+        let t = (f32(i) + 0.5) / f32(AO_SAMPLES);
+        let angle = f32(i) * 2.3999632 + rotation;
+        let r = sqrt(t);
+        let direction = (tangent * cos(angle) + bitangent * sin(angle)) * r + normal * sqrt(1.0 - t);
+        // Sample distances are shuffled so they don't follow the spiral
+        let scale = mix(0.2, 1.0, f32((i * 7u) % AO_SAMPLES) / f32(AO_SAMPLES));
+        let sample_position = position + normal * 0.02 + direction * AO_RADIUS * scale;
+
+        var blocked = 0.0;
+        let clip = camera.view_proj * vec4<f32>(sample_position, 1.0);
+        if clip.w > 0.0 {
+            let ndc = clip.xy / clip.w;
+            let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+            if all(uv >= vec2<f32>(0.0)) && all(uv < vec2<f32>(1.0)) {
+                let scene_depth = textureLoad(depthTexture, vec2<i32>(uv * size), 0).r;
+                let scene_position = world_position(uv, scene_depth);
+                let in_front = distance(scene_position, camera.position.xyz) < distance(sample_position, camera.position.xyz) - 0.02;
+                // Geometry far in front of the point, like a distant pillar, doesn't block it
+                let nearby = 1.0 - smoothstep(AO_RADIUS, AO_RADIUS * 2.0, distance(scene_position, position));
+                blocked = select(0.0, nearby, in_front && scene_depth < 1.0);
+            }
+        }
+        open += 1.0 - blocked;
+        bent += direction * (1.0 - blocked);
+    }
+    let bent_normal = select(normal, normalize(bent), length(bent) > 0.0001);
+    return vec4<f32>(bent_normal, open / f32(AO_SAMPLES));
+}
+#endif
+
+fn sky_color(view_direction: vec3f) -> vec3f {
+    let height = clamp(view_direction.y, 0.0, 1.0);
+    return mix(sky.fog_color.rgb, sky.zenith_color.rgb, sqrt(height));
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let coords = vec2<i32>(in.clip_position.xy);
+    let depth = textureLoad(depthTexture, coords, 0).r;
+
+    let position = world_position(in.uv, depth);
+    let to_pixel = position - camera.position.xyz;
+    let view_direction = normalize(to_pixel);
+
+    // Nothing was drawn here. The cleared depth doesn't unproject to a point in
+    // front of the camera, so the sky's direction comes from depth 0 instead.
+    if depth >= 1.0 {
+        let sky_direction = normalize(world_position(in.uv, 0.0) - camera.position.xyz);
+        return vec4<f32>(sky_color(sky_direction), 1.0);
+    }
+
     var input: GBufferOutput;
-    input.normal = textureSample(normalTexture, normalSampler, in.clip_position.xy);
-    input.color = textureSample(colorTexture, colorSampler, in.clip_position.xy);
+    input.normal = textureLoad(normalTexture, coords, 0);
+    input.color = textureLoad(colorTexture, coords, 0);
 
-    // TODO
+    let normal = normalize(input.normal.xyz);
+    let diffuse = max(dot(normal, sky.sun_direction.xyz), 0.0);
+    var ambient = sky.ambient_color.rgb;
+#ifdef SSAO
+    let occlusion = ambient_occlusion(coords, position, normal);
+#ifdef BENT_NORMALS
+    // Ambient light comes from the sky, so surfaces open upwards get more
+    ambient *= mix(0.6, 1.4, occlusion.y * 0.5 + 0.5);
+#endif
+    ambient *= occlusion.w;
+#endif
+    let light = ambient + sky.sun_color.rgb * diffuse + point_lighting(position, normal);
+    let lit = input.color.rgb * light;
 
-    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
-}
\ No newline at end of file
+    let fog = smoothstep(sky.fog_range.x, sky.fog_range.y, length(to_pixel));
+    return vec4<f32>(mix(lit, sky_color(view_direction), fog), 1.0);
+}
@@ -0,0 +1,78 @@
+struct ReflectionUniform {
+    view_proj: mat4x4f, // mirrored about the plane
+    camera_position: vec4f, // mirrored about the plane
+    plane_height: vec4f, // x: height; y, z, w unused
+};
+@group(0) @binding(0)
+var<uniform> reflection: ReflectionUniform;
+
+struct SkyUniform {
+    sun_direction: vec4f,
+    sun_color: vec4f,
+    ambient_color: vec4f,
+    fog_color: vec4f,
+    zenith_color: vec4f,
+    fog_range: vec4f, // x: start, y: end
+};
+@group(1) @binding(0)
+var<uniform> sky: SkyUniform;
+
+// Preprocessed before compiling; see draw_data.rs
+struct DrawUniform {
+    offset: vec3f,
+    fade: f32,
+    id: u32, // The object ID
+};
+#ifdef PUSH_CONSTANTS
+var<push_constant> draw: DrawUniform;
+#else
+@group(2) @binding(0)
+var<uniform> draw: DrawUniform;
+#endif
+
+struct VertexInput {
+    @location(0) position: vec3f,
+    @location(1) color: vec3f,
+    @location(2) normal: vec3f,
+    @location(3) smoothness: f32
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) color: vec3f,
+    @location(1) normal: vec3f,
+    @location(2) world_position: vec3f
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let world_position = model.position + draw.offset;
+    out.clip_position = reflection.view_proj * vec4<f32>(world_position, 1.0);
+    out.color = model.color;
+    out.normal = model.normal;
+    out.world_position = world_position;
+    return out;
+}
+
+// A cheaper version of the lighting pass: sun, ambient and fog only.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    // Anything under the plane can't be seen in its reflection. The
+    // reflection is too blurry for dithering, so fading meshes pop in halfway.
+    if in.world_position.y < reflection.plane_height.x || draw.fade < 0.5 {
+        discard;
+    }
+
+    let normal = normalize(in.normal);
+    let diffuse = max(dot(normal, sky.sun_direction.xyz), 0.0);
+    let lit = in.color * (sky.ambient_color.rgb + sky.sun_color.rgb * diffuse);
+
+    let fog = smoothstep(sky.fog_range.x, sky.fog_range.y, distance(in.world_position, reflection.camera_position.xyz));
+    // Alpha marks where something was drawn; the rest reflects the sky.
+    // Color is premultiplied so filtering doesn't darken the edges.
+    let coverage = 1.0 - fog;
+    return vec4<f32>(lit * coverage, coverage);
+}
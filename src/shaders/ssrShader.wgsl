@@ -0,0 +1,167 @@
+@group(0) @binding(0)
+var normalTexture: texture_2d<f32>;
+@group(0) @binding(1)
+var colorTexture: texture_2d<f32>;
+@group(0) @binding(2)
+var depthTexture: texture_2d<f32>;
+
+struct CameraUniform {
+    view_proj: mat4x4f,
+    inv_view_proj: mat4x4f,
+    position: vec4f,
+};
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct SkyUniform {
+    sun_direction: vec4f,
+    sun_color: vec4f,
+    ambient_color: vec4f,
+    fog_color: vec4f,
+    zenith_color: vec4f,
+    fog_range: vec4f, // x: start, y: end
+};
+@group(2) @binding(0)
+var<uniform> sky: SkyUniform;
+
+struct SsrSettings {
+    max_steps: u32, // 0 disables reflections
+    refine_steps: u32,
+    max_distance: f32,
+    thickness: f32,
+};
+@group(3) @binding(0)
+var sceneTexture: texture_2d<f32>;
+@group(3) @binding(1)
+var<uniform> settings: SsrSettings;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) uv: vec2f,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) id: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    var uv = vec2<f32>(f32((id << 1) & 2), f32(id & 2));
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2, -2) + vec2<f32>(-1, 1), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn world_position(uv: vec2f, depth: f32) -> vec3f {
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = camera.inv_view_proj * ndc;
+    return world.xyz / world.w;
+}
+
+fn sky_color(view_direction: vec3f) -> vec3f {
+    let height = clamp(view_direction.y, 0.0, 1.0);
+    return mix(sky.fog_color.rgb, sky.zenith_color.rgb, sqrt(height));
+}
+
+struct RayStep {
+    uv: vec2f,
+    behind: f32,
+    on_screen: bool,
+};
+
+// Projects a world-space point to screen uv and finds how far behind
+// (positive) or in front of (negative) the depth buffer it is, in world units.
+fn ray_step(point: vec3f, size: vec2f) -> RayStep {
+    var result: RayStep;
+    let clip = camera.view_proj * vec4<f32>(point, 1.0);
+    if clip.w <= 0.0 {
+        result.on_screen = false;
+        return result;
+    }
+    let ndc = clip.xyz / clip.w;
+    result.uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    result.on_screen = all(result.uv >= vec2<f32>(0.0)) && all(result.uv < vec2<f32>(1.0));
+    if !result.on_screen {
+        return result;
+    }
+
+    let scene_depth = textureLoad(depthTexture, vec2<i32>(result.uv * size), 0).r;
+    let scene_distance = distance(world_position(result.uv, scene_depth), camera.position.xyz);
+    result.behind = distance(point, camera.position.xyz) - scene_distance;
+    return result;
+}
+
+// Marches a reflected ray through the depth buffer. Returns the reflected
+// color, falling back to the sky when the ray misses or leaves the screen.
+fn trace_reflection(origin: vec3f, direction: vec3f, size: vec2f) -> vec3f {
+    let step_length = settings.max_distance / f32(settings.max_steps);
+    var previous = 0.0;
+    for (var i = 1u; i <= settings.max_steps; i++) {
+        let travelled = f32(i) * step_length;
+        let current = ray_step(origin + direction * travelled, size);
+        if !current.on_screen {
+            break;
+        }
+        if current.behind > 0.0 {
+            if current.behind > settings.thickness + step_length {
+                // Passed behind something much closer to the camera; what's
+                // really there is hidden
+                break;
+            }
+
+            // Binary search between the last two steps for the surface
+            var low = previous;
+            var high = travelled;
+            var hit = current;
+            for (var j = 0u; j < settings.refine_steps; j++) {
+                let middle = (low + high) * 0.5;
+                let refined = ray_step(origin + direction * middle, size);
+                if refined.on_screen && refined.behind > 0.0 {
+                    high = middle;
+                    hit = refined;
+                } else {
+                    low = middle;
+                }
+            }
+
+            // Fade out near the screen edge so reflections don't pop
+            let edge = min(min(hit.uv.x, 1.0 - hit.uv.x), min(hit.uv.y, 1.0 - hit.uv.y));
+            let fade = smoothstep(0.0, 0.1, edge);
+            let color = textureLoad(sceneTexture, vec2<i32>(hit.uv * size), 0).rgb;
+            return mix(sky_color(direction), color, fade);
+        }
+        previous = travelled;
+    }
+    return sky_color(direction);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let coords = vec2<i32>(in.clip_position.xy);
+    let scene = textureLoad(sceneTexture, coords, 0).rgb;
+    let normal_smoothness = textureLoad(normalTexture, coords, 0);
+    let smoothness = normal_smoothness.a;
+    let depth = textureLoad(depthTexture, coords, 0).r;
+    if settings.max_steps == 0u || smoothness <= 0.0 || depth >= 1.0 {
+        return vec4<f32>(scene, 1.0);
+    }
+
+    let size = vec2<f32>(textureDimensions(sceneTexture));
+    let position = world_position(in.uv, depth);
+    let to_pixel = position - camera.position.xyz;
+    let view_direction = normalize(to_pixel);
+    let normal = normalize(normal_smoothness.xyz);
+    let direction = reflect(view_direction, normal);
+
+    // Schlick's approximation: surfaces reflect more at glancing angles
+    let f0 = 0.04;
+    let cos_theta = clamp(dot(-view_direction, normal), 0.0, 1.0);
+    let fresnel = f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+
+    // The scene color is already fogged, so fade reflections with the same fog
+    let fog = smoothstep(sky.fog_range.x, sky.fog_range.y, length(to_pixel));
+    let strength = smoothness * mix(fresnel, 1.0, smoothness * 0.5) * (1.0 - fog);
+
+    // Start slightly off the surface so the ray doesn't hit itself
+    let reflection = trace_reflection(position + normal * 0.05, direction, size);
+    return vec4<f32>(mix(scene, reflection, strength), 1.0);
+}
@@ -0,0 +1,277 @@
+// Preprocessed before compiling; see shader_preprocessor.rs. Defines:
+// PLANAR_REFLECTIONS samples the planar reflection texture, SSR_REFLECTIONS
+// ray marches the scene, and with neither only the sky is reflected.
+
+@group(0) @binding(0)
+var normalTexture: texture_2d<f32>;
+@group(0) @binding(1)
+var colorTexture: texture_2d<f32>;
+@group(0) @binding(2)
+var depthTexture: texture_2d<f32>;
+
+struct CameraUniform {
+    view_proj: mat4x4f,
+    inv_view_proj: mat4x4f,
+    position: vec4f,
+};
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct SkyUniform {
+    sun_direction: vec4f,
+    sun_color: vec4f,
+    ambient_color: vec4f,
+    fog_color: vec4f,
+    zenith_color: vec4f,
+    fog_range: vec4f, // x: start, y: end
+};
+@group(2) @binding(0)
+var<uniform> sky: SkyUniform;
+
+struct WaterUniform {
+    time: f32,
+    level: f32,
+    max_steps: u32,
+    refine_steps: u32,
+};
+@group(3) @binding(0)
+var<uniform> water: WaterUniform;
+// The lit scene without water, for refraction
+@group(3) @binding(1)
+var sceneTexture: texture_2d<f32>;
+@group(3) @binding(2)
+var planarTexture: texture_2d<f32>;
+@group(3) @binding(3)
+var planarSampler: sampler;
+
+const WATER_COLOR = vec3<f32>(0.02, 0.12, 0.16);
+// How quickly each channel is absorbed per unit of water; red goes first
+const ABSORPTION = vec3<f32>(0.45, 0.09, 0.06);
+const FOAM_DEPTH = 0.6;
+const REFLECTION_DISTANCE = 40.0;
+const REFLECTION_THICKNESS = 1.0;
+
+struct VertexInput {
+    @location(0) position: vec2f, // x and z; y is the water level
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) world_position: vec3f,
+    @location(1) normal: vec3f,
+}
+
+struct Wave {
+    direction: vec2f,
+    wavelength: f32,
+    steepness: f32,
+};
+
+const WAVE_COUNT = 3;
+const WAVES = array<Wave, WAVE_COUNT>(
+    Wave(vec2<f32>(1.0, 0.0), 9.0, 0.12),
+    Wave(vec2<f32>(0.6, 0.8), 5.0, 0.1),
+    Wave(vec2<f32>(-0.7, 0.7), 3.1, 0.08),
+);
+
+@vertex
+fn vs_main(
+    in: VertexInput,
+) -> VertexOutput {
+    // Gerstner waves: points move in circles, bunching up at the crests
+    var position = vec3<f32>(in.position.x, water.level, in.position.y);
+    var tangent = vec3<f32>(1.0, 0.0, 0.0);
+    var binormal = vec3<f32>(0.0, 0.0, 1.0);
+    for (var i = 0; i < WAVE_COUNT; i++) {
+        let wave = WAVES[i];
+        let k = 6.2831853 / wave.wavelength;
+        let speed = sqrt(9.8 / k);
+        let d = normalize(wave.direction);
+        let f = k * (dot(d, in.position) - speed * water.time);
+        let amplitude = wave.steepness / k;
+
+        position += vec3<f32>(d.x * amplitude * cos(f), amplitude * sin(f), d.y * amplitude * cos(f));
+        tangent += vec3<f32>(
+            -d.x * d.x * wave.steepness * sin(f),
+            d.x * wave.steepness * cos(f),
+            -d.x * d.y * wave.steepness * sin(f)
+        );
+        binormal += vec3<f32>(
+            -d.x * d.y * wave.steepness * sin(f),
+            d.y * wave.steepness * cos(f),
+            -d.y * d.y * wave.steepness * sin(f)
+        );
+    }
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+    out.world_position = position;
+    out.normal = normalize(cross(binormal, tangent));
+    return out;
+}
+
+fn world_position(uv: vec2f, depth: f32) -> vec3f {
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = camera.inv_view_proj * ndc;
+    return world.xyz / world.w;
+}
+
+fn sky_color(view_direction: vec3f) -> vec3f {
+    let height = clamp(view_direction.y, 0.0, 1.0);
+    return mix(sky.fog_color.rgb, sky.zenith_color.rgb, sqrt(height));
+}
+
+// Small ripples too fine for the mesh, scrolling in a few directions.
+fn ripple_normal(position: vec2f, normal: vec3f) -> vec3f {
+    let t = water.time;
+    let a = cos(dot(position, vec2<f32>(1.7, 0.9)) * 2.3 + t * 1.9);
+    let b = cos(dot(position, vec2<f32>(-1.1, 1.6)) * 3.1 + t * 2.3);
+    let c = cos(dot(position, vec2<f32>(0.4, -1.9)) * 4.7 + t * 3.1);
+    let slope = vec2<f32>(1.7, 0.9) * a * 0.03 + vec2<f32>(-1.1, 1.6) * b * 0.02 + vec2<f32>(0.4, -1.9) * c * 0.012;
+    return normalize(normal + vec3<f32>(slope.x, 0.0, slope.y));
+}
+
+#ifdef SSR_REFLECTIONS
+struct RayStep {
+    uv: vec2f,
+    behind: f32,
+    on_screen: bool,
+};
+
+// Projects a world-space point to screen uv and finds how far behind
+// (positive) or in front of (negative) the depth buffer it is, in world units.
+fn ray_step(point: vec3f, size: vec2f) -> RayStep {
+    var result: RayStep;
+    let clip = camera.view_proj * vec4<f32>(point, 1.0);
+    if clip.w <= 0.0 {
+        result.on_screen = false;
+        return result;
+    }
+    let ndc = clip.xyz / clip.w;
+    result.uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    result.on_screen = all(result.uv >= vec2<f32>(0.0)) && all(result.uv < vec2<f32>(1.0));
+    if !result.on_screen {
+        return result;
+    }
+
+    let scene_depth = textureLoad(depthTexture, vec2<i32>(result.uv * size), 0).r;
+    let scene_distance = distance(world_position(result.uv, scene_depth), camera.position.xyz);
+    result.behind = distance(point, camera.position.xyz) - scene_distance;
+    return result;
+}
+
+// The same march as the SSR pass, against the scene without water.
+fn trace_reflection(origin: vec3f, direction: vec3f, size: vec2f) -> vec3f {
+    let step_length = REFLECTION_DISTANCE / f32(water.max_steps);
+    var previous = 0.0;
+    for (var i = 1u; i <= water.max_steps; i++) {
+        let travelled = f32(i) * step_length;
+        let current = ray_step(origin + direction * travelled, size);
+        if !current.on_screen {
+            break;
+        }
+        if current.behind > 0.0 {
+            if current.behind > REFLECTION_THICKNESS + step_length {
+                break;
+            }
+
+            var low = previous;
+            var high = travelled;
+            var hit = current;
+            for (var j = 0u; j < water.refine_steps; j++) {
+                let middle = (low + high) * 0.5;
+                let refined = ray_step(origin + direction * middle, size);
+                if refined.on_screen && refined.behind > 0.0 {
+                    high = middle;
+                    hit = refined;
+                } else {
+                    low = middle;
+                }
+            }
+
+            let edge = min(min(hit.uv.x, 1.0 - hit.uv.x), min(hit.uv.y, 1.0 - hit.uv.y));
+            let fade = smoothstep(0.0, 0.1, edge);
+            let color = textureLoad(sceneTexture, vec2<i32>(hit.uv * size), 0).rgb;
+            return mix(sky_color(direction), color, fade);
+        }
+        previous = travelled;
+    }
+    return sky_color(direction);
+}
+#endif
+
+// How much water a view ray passes through before hitting what's behind the
+// surface at `coords`. Very large when nothing is behind it.
+fn water_thickness(coords: vec2<i32>, size: vec2f, surface: vec3f) -> f32 {
+    let depth = textureLoad(depthTexture, coords, 0).r;
+    if depth >= 1.0 {
+        return 1000.0;
+    }
+    let behind = world_position((vec2<f32>(coords) + 0.5) / size, depth);
+    return distance(behind, surface);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let size = vec2<f32>(textureDimensions(sceneTexture));
+    let coords = vec2<i32>(in.clip_position.xy);
+    let uv = in.clip_position.xy / size;
+
+    // Opaque geometry has already been drawn, so depth test by hand
+    if in.clip_position.z >= textureLoad(depthTexture, coords, 0).r {
+        discard;
+    }
+
+    let to_surface = in.world_position - camera.position.xyz;
+    let view_direction = normalize(to_surface);
+    let normal = ripple_normal(in.world_position.xz, normalize(in.normal));
+
+    // Refraction: bend the view of what's behind, unless that would pull in
+    // something in front of the water
+    var refracted_coords = clamp(
+        coords + vec2<i32>(normal.xz * 0.04 * size.y),
+        vec2<i32>(0), vec2<i32>(size) - 1
+    );
+    if textureLoad(depthTexture, refracted_coords, 0).r <= in.clip_position.z {
+        refracted_coords = coords;
+    }
+    let thickness = water_thickness(refracted_coords, size, in.world_position);
+    let behind = textureLoad(sceneTexture, refracted_coords, 0).rgb;
+
+    // Absorption: light from behind fades into the water's own scattered color
+    let transmittance = exp(-ABSORPTION * thickness);
+    let sun_amount = max(dot(normal, sky.sun_direction.xyz), 0.0);
+    let scattered = WATER_COLOR * (sky.ambient_color.rgb + sky.sun_color.rgb * sun_amount);
+    var color = behind * transmittance + scattered * (1.0 - transmittance);
+
+    let direction = reflect(view_direction, normal);
+#ifdef PLANAR_REFLECTIONS
+    // The planar reflection lines up with the screen; ripples distort it
+    let planar = textureSampleLevel(planarTexture, planarSampler, uv + normal.xz * 0.02, 0.0);
+    var reflection = planar.rgb + sky_color(direction) * (1.0 - planar.a);
+#else
+#ifdef SSR_REFLECTIONS
+    var reflection = trace_reflection(in.world_position + normal * 0.05, direction, size);
+#else
+    var reflection = sky_color(direction);
+#endif
+#endif
+    let specular = pow(max(dot(direction, sky.sun_direction.xyz), 0.0), 200.0) * sky.sun_color.rgb;
+    reflection += specular * 2.0;
+
+    // Schlick's approximation with water's reflectance
+    let f0 = 0.02;
+    let cos_theta = clamp(dot(-view_direction, normal), 0.0, 1.0);
+    let fresnel = f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+    color = mix(color, reflection, fresnel);
+
+    // Foam where the water is shallow, broken up so it doesn't look like a band
+    let foam_pattern = 0.5 + 0.5 * sin(in.world_position.x * 3.7 + water.time * 1.3)
+        * sin(in.world_position.z * 4.3 - water.time * 0.9);
+    let foam = (1.0 - smoothstep(0.0, FOAM_DEPTH, thickness)) * mix(0.6, 1.0, foam_pattern);
+    let foam_color = sky.ambient_color.rgb + sky.sun_color.rgb * max(sky.sun_direction.y, 0.0);
+    color = mix(color, foam_color, foam);
+
+    let fog = smoothstep(sky.fog_range.x, sky.fog_range.y, length(to_surface));
+    return vec4<f32>(mix(color, sky_color(view_direction), fog), 1.0);
+}
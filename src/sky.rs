@@ -0,0 +1,127 @@
+use cgmath::{InnerSpace, Vector3};
+use serde::Deserialize;
+
+use crate::resources;
+
+/// The lighting colors at one point in the day.
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct SkyColors {
+    pub sun: [f32; 3],
+    pub ambient: [f32; 3],
+    /// The sky color at the horizon, which distant terrain also fades into.
+    pub fog: [f32; 3],
+    /// The sky color straight up.
+    pub zenith: [f32; 3],
+    /// Strength of light shafts from the sun.
+    pub godrays: f32,
+}
+
+impl SkyColors {
+    fn lerp(&self, other: &SkyColors, t: f32) -> SkyColors {
+        let mix = |a: [f32; 3], b: [f32; 3]| std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t);
+        SkyColors {
+            sun: mix(self.sun, other.sun),
+            ambient: mix(self.ambient, other.ambient),
+            fog: mix(self.fog, other.fog),
+            zenith: mix(self.zenith, other.zenith),
+            godrays: self.godrays + (other.godrays - self.godrays) * t,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Keyframe {
+    /// Time of day from 0 to 1, where 0 is midnight and 0.5 is noon.
+    time: f32,
+    colors: SkyColors,
+}
+
+/// Sky colors keyed by time of day, blended linearly between keyframes.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SkyGradient {
+    keyframes: Vec<Keyframe>,
+}
+
+impl SkyGradient {
+    pub async fn load(file_name: &str) -> anyhow::Result<SkyGradient> {
+        let text = resources::load_string(file_name).await?;
+        let mut gradient: SkyGradient = ron::from_str(&text)?;
+        if gradient.keyframes.is_empty() {
+            anyhow::bail!("{} has no keyframes", file_name);
+        }
+        gradient.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(gradient)
+    }
+
+    pub fn sample(&self, time: f32) -> SkyColors {
+        let keyframes = &self.keyframes;
+        // The first keyframe after `time`, wrapping around midnight
+        let next_index = keyframes.iter().position(|k| k.time > time).unwrap_or(0);
+        let next = &keyframes[next_index];
+        let previous = &keyframes[(next_index + keyframes.len() - 1) % keyframes.len()];
+
+        let span = (next.time - previous.time).rem_euclid(1.0);
+        if span == 0.0 {
+            return previous.colors;
+        }
+        let t = (time - previous.time).rem_euclid(1.0) / span;
+        previous.colors.lerp(&next.colors, t)
+    }
+}
+
+/// The in-game clock.
+pub struct TimeOfDay {
+    /// From 0 to 1, where 0 is midnight and 0.5 is noon.
+    pub time: f32,
+    /// Real seconds per in-game day.
+    pub day_length: f32,
+}
+
+impl TimeOfDay {
+    pub fn new(time: f32, day_length: f32) -> Self {
+        Self { time, day_length }
+    }
+
+    pub fn advance(&mut self, delta_time: f32) {
+        self.time = (self.time + delta_time / self.day_length).rem_euclid(1.0);
+    }
+
+    /// Skips forward (or back, if negative) by a number of in-game hours.
+    pub fn skip_hours(&mut self, hours: f32) {
+        self.time = (self.time + hours / 24.0).rem_euclid(1.0);
+    }
+
+    /// The direction towards the sun. It rises in +x at 0.25, is overhead
+    /// at noon and sets in -x at 0.75, with a slight tilt so it never
+    /// passes exactly overhead.
+    pub fn sun_direction(&self) -> Vector3<f32> {
+        let angle = (self.time - 0.25) * std::f32::consts::TAU;
+        Vector3::new(angle.cos(), angle.sin(), 0.3).normalize()
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyUniform {
+    sun_direction: [f32; 4],
+    sun_color: [f32; 4],
+    ambient_color: [f32; 4],
+    fog_color: [f32; 4],
+    zenith_color: [f32; 4],
+    /// x: distance fog starts, y: distance fog is fully opaque
+    fog_range: [f32; 4],
+}
+
+impl SkyUniform {
+    pub fn new(time: &TimeOfDay, colors: &SkyColors, fog_start: f32, fog_end: f32) -> Self {
+        let extend = |c: [f32; 3]| [c[0], c[1], c[2], 1.0];
+        Self {
+            sun_direction: time.sun_direction().extend(0.0).into(),
+            sun_color: extend(colors.sun),
+            ambient_color: extend(colors.ambient),
+            fog_color: extend(colors.fog),
+            zenith_color: extend(colors.zenith),
+            fog_range: [fog_start, fog_end, 0.0, 0.0],
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Screen-space reflections. Reflective pixels march a ray through the
+//! G-buffer's depth and pick up the lit color of whatever they hit, falling
+//! back to the sky when the ray leaves the screen. This pass also writes the
+//! lit scene to the screen, so it always runs even with reflections off.
+//! Water does its own reflections and isn't in the G-buffer, so it's skipped.
+
+use crate::{settings::ReflectionMode, texture::Texture};
+
+fn settings(mode: ReflectionMode) -> SsrSettings {
+    let (max_steps, refine_steps) = mode.ssr_steps();
+    SsrSettings { max_steps, refine_steps, max_distance: 40.0, thickness: 1.0 }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SsrSettings {
+    max_steps: u32,
+    refine_steps: u32,
+    /// How far a reflected ray travels before giving up, in world units.
+    max_distance: f32,
+    /// How far behind the depth buffer a ray can be and still count as a hit.
+    thickness: f32,
+}
+
+pub struct ScreenSpaceReflections {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    settings_buffer: wgpu::Buffer,
+}
+
+impl ScreenSpaceReflections {
+    /// `gbuf_layout`, `camera_layout` and `sky_layout` are bound at groups 0-2
+    /// when drawing; the scene texture and settings go in group 3.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        gbuf_layout: &wgpu::BindGroupLayout,
+        camera_layout: &wgpu::BindGroupLayout,
+        sky_layout: &wgpu::BindGroupLayout,
+        scene_texture: &Texture
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SSR Bind Group Layout"),
+            entries: &[
+                // 0: lit scene
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                // 1: settings
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ]
+        });
+        let settings_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SSR Settings Buffer"),
+            size: std::mem::size_of::<SsrSettings>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &settings_buffer, scene_texture);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/ssrShader.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("SSR Pipeline Layout"),
+            bind_group_layouts: &[gbuf_layout, camera_layout, sky_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("SSR Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None
+        });
+
+        Self { pipeline, bind_group_layout, bind_group, settings_buffer }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        settings_buffer: &wgpu::Buffer,
+        scene_texture: &Texture
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&scene_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: settings_buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("SSR Bind Group"),
+        })
+    }
+
+    /// Points the pass at a new scene texture after a resize.
+    pub fn resize(&mut self, device: &wgpu::Device, scene_texture: &Texture) {
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.settings_buffer, scene_texture);
+    }
+
+    pub fn prepare(&self, queue: &wgpu::Queue, mode: ReflectionMode) {
+        queue.write_buffer(&self.settings_buffer, 0, bytemuck::cast_slice(&[settings(mode)]));
+    }
+
+    pub fn draw(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        gbuf_bind_group: &wgpu::BindGroup,
+        camera_bind_group: &wgpu::BindGroup,
+        sky_bind_group: &wgpu::BindGroup
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, gbuf_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, sky_bind_group, &[]);
+        render_pass.set_bind_group(3, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
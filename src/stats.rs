@@ -0,0 +1,98 @@
+//! Lifetime gameplay statistics, counted from gameplay events and kept in
+//! the player save. F7 shows them.
+
+use cgmath::MetricSpace;
+use serde::{Deserialize, Serialize};
+
+use crate::{events::{EventBus, GameEvent, Subscription}, overlay::Overlay};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    /// Seconds played.
+    pub playtime: f64,
+    /// Horizontal distance covered.
+    pub distance_travelled: f64,
+    /// Total height lost going down, whether falling or swimming.
+    pub distance_descended: f64,
+    /// Seconds spent with the camera underwater.
+    pub time_underwater: f64,
+    pub torches_lit: u32,
+    pub sunrises_seen: u32,
+}
+
+pub struct StatsTracker {
+    pub stats: Stats,
+    events: Subscription,
+    submerged: bool,
+    /// Whether the stats screen is showing.
+    pub open: bool,
+}
+
+impl StatsTracker {
+    const TEXT_SCALE: f32 = 2.0;
+
+    pub fn new(stats: Stats, events: &EventBus) -> Self {
+        Self { stats, events: events.subscribe(), submerged: false, open: false }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        for event in self.events.drain() {
+            match event {
+                GameEvent::PlayerMoved { from, to } => {
+                    let horizontal = |p: cgmath::Point3<f32>| cgmath::Point2::new(p.x, p.z);
+                    self.stats.distance_travelled += horizontal(from).distance(horizontal(to)) as f64;
+                    self.stats.distance_descended += (from.y - to.y).max(0.0) as f64;
+                }
+                GameEvent::Submerged { submerged } => self.submerged = submerged,
+                GameEvent::TorchToggled { held: true } => self.stats.torches_lit += 1,
+                GameEvent::DaylightChanged { day: true } => self.stats.sunrises_seen += 1,
+                _ => {}
+            }
+        }
+
+        self.stats.playtime += delta_time as f64;
+        if self.submerged {
+            self.stats.time_underwater += delta_time as f64;
+        }
+    }
+
+    /// Formats seconds as hours, minutes and seconds.
+    fn duration(seconds: f64) -> String {
+        let seconds = seconds as u64;
+        format!("{}h {:02}m {:02}s", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    }
+
+    /// The stats screen, centered, if it's open.
+    pub fn draw(&self, overlay: &mut Overlay, size: winit::dpi::PhysicalSize<u32>) {
+        if !self.open {
+            return;
+        }
+
+        let stats = &self.stats;
+        let lines = [
+            ("Time played", Self::duration(stats.playtime)),
+            ("Distance travelled", format!("{:.0} blocks", stats.distance_travelled)),
+            ("Distance descended", format!("{:.0} blocks", stats.distance_descended)),
+            ("Time underwater", Self::duration(stats.time_underwater)),
+            ("Torches lit", stats.torches_lit.to_string()),
+            ("Sunrises seen", stats.sunrises_seen.to_string()),
+        ];
+
+        let line_height = Overlay::line_height(Self::TEXT_SCALE) + 6.0;
+        let char_width = Overlay::line_height(Self::TEXT_SCALE);
+        let width = 40.0 * char_width;
+        let height = line_height * (lines.len() + 2) as f32;
+        let x = (size.width as f32 - width) / 2.0;
+        let y = (size.height as f32 - height) / 2.0;
+
+        overlay.rect(x - 12.0, y - 12.0, width + 24.0, height + 24.0, [0.0, 0.0, 0.0, 0.8]);
+        overlay.text(x, y, "Statistics", Self::TEXT_SCALE, [1.0, 0.85, 0.3, 1.0]);
+        for (i, (label, value)) in lines.iter().enumerate() {
+            let row = y + line_height * (i + 2) as f32;
+            overlay.text(x, row, label, Self::TEXT_SCALE, [0.8, 0.8, 0.8, 1.0]);
+            let value_x = x + width - value.len() as f32 * char_width;
+            overlay.text(value_x, row, value, Self::TEXT_SCALE, [1.0, 1.0, 1.0, 1.0]);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Timed effects on the player, shown in the bottom left and kept in the
+//! player save. Each effect is applied by the system it changes, which asks
+//! `StatusEffects` for its current modifier every frame. Nothing grants
+//! effects yet; F8 applies all of them, for testing.
+
+use serde::{Deserialize, Serialize};
+
+use crate::overlay::Overlay;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusEffect {
+    Speed,
+    Slowness,
+    /// Dark scenes are exposed as if they were lit.
+    NightVision,
+}
+
+impl StatusEffect {
+    pub const ALL: [StatusEffect; 3] = [StatusEffect::Speed, StatusEffect::Slowness, StatusEffect::NightVision];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            StatusEffect::Speed => "Speed",
+            StatusEffect::Slowness => "Slowness",
+            StatusEffect::NightVision => "Night Vision",
+        }
+    }
+
+    /// The color of the effect's icon.
+    fn color(&self) -> [f32; 4] {
+        match self {
+            StatusEffect::Speed => [0.4, 0.8, 1.0, 1.0],
+            StatusEffect::Slowness => [0.45, 0.45, 0.6, 1.0],
+            StatusEffect::NightVision => [0.3, 0.3, 1.0, 1.0],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActiveEffect {
+    pub effect: StatusEffect,
+    /// Seconds left.
+    pub remaining: f32,
+}
+
+pub struct StatusEffects {
+    active: Vec<ActiveEffect>,
+}
+
+impl StatusEffects {
+    const TEXT_SCALE: f32 = 2.0;
+    const SPEED_MULTIPLIER: f32 = 1.5;
+    const SLOWNESS_MULTIPLIER: f32 = 0.5;
+    /// Night vision never lets exposure fall below this.
+    const NIGHT_VISION_EXPOSURE: f32 = 3.0;
+
+    /// `active` holds the effects from the save file.
+    pub fn new(active: Vec<ActiveEffect>) -> Self {
+        Self { active }
+    }
+
+    /// The active effects, for the save file.
+    pub fn active(&self) -> Vec<ActiveEffect> {
+        self.active.clone()
+    }
+
+    /// Starts `effect`, or extends it if it would otherwise end sooner.
+    pub fn add(&mut self, effect: StatusEffect, duration: f32) {
+        tracing::info!("Status effect {} for {}s", effect.label(), duration);
+        match self.active.iter_mut().find(|active| active.effect == effect) {
+            Some(active) => active.remaining = active.remaining.max(duration),
+            None => self.active.push(ActiveEffect { effect, remaining: duration }),
+        }
+    }
+
+    pub fn has(&self, effect: StatusEffect) -> bool {
+        self.active.iter().any(|active| active.effect == effect)
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        for active in &mut self.active {
+            active.remaining -= delta_time;
+        }
+        self.active.retain(|active| {
+            if active.remaining <= 0.0 {
+                tracing::info!("Status effect {} wore off", active.effect.label());
+            }
+            active.remaining > 0.0
+        });
+    }
+
+    /// How much faster than normal the player moves.
+    pub fn speed_multiplier(&self) -> f32 {
+        let mut multiplier = 1.0;
+        if self.has(StatusEffect::Speed) {
+            multiplier *= Self::SPEED_MULTIPLIER;
+        }
+        if self.has(StatusEffect::Slowness) {
+            multiplier *= Self::SLOWNESS_MULTIPLIER;
+        }
+        multiplier
+    }
+
+    /// The lowest exposure allowed, given the setting's own minimum.
+    pub fn min_exposure(&self, min_exposure: f32) -> f32 {
+        if self.has(StatusEffect::NightVision) {
+            min_exposure.max(Self::NIGHT_VISION_EXPOSURE)
+        } else {
+            min_exposure
+        }
+    }
+
+    /// Lists the active effects in the bottom left, each with a colored icon
+    /// and the time left.
+    pub fn draw(&self, overlay: &mut Overlay, size: winit::dpi::PhysicalSize<u32>) {
+        let line_height = Overlay::line_height(Self::TEXT_SCALE) + 8.0;
+        let icon_size = Overlay::line_height(Self::TEXT_SCALE);
+        for (i, active) in self.active.iter().rev().enumerate() {
+            let y = size.height as f32 - 16.0 - line_height * (i + 1) as f32;
+            let seconds = active.remaining.ceil() as u32;
+            let text = format!("{} {}:{:02}", active.effect.label(), seconds / 60, seconds % 60);
+            // Glyphs are square, so a character is as wide as a line is high
+            let width = icon_size + 8.0 + text.len() as f32 * icon_size;
+            overlay.rect(12.0, y - 4.0, width + 8.0, line_height, [0.0, 0.0, 0.0, 0.5]);
+            overlay.rect(16.0, y, icon_size, icon_size, active.effect.color());
+            overlay.text(16.0 + icon_size + 8.0, y, &text, Self::TEXT_SCALE, [1.0, 1.0, 1.0, 1.0]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wears_off() {
+        let mut effects = StatusEffects::new(Vec::new());
+        effects.add(StatusEffect::Speed, 1.0);
+        effects.update(0.6);
+        assert!(effects.has(StatusEffect::Speed));
+        assert_eq!(effects.speed_multiplier(), StatusEffects::SPEED_MULTIPLIER);
+        effects.update(0.6);
+        assert!(!effects.has(StatusEffect::Speed));
+        assert_eq!(effects.speed_multiplier(), 1.0);
+    }
+
+    #[test]
+    fn adding_again_keeps_the_longer_time() {
+        let mut effects = StatusEffects::new(Vec::new());
+        effects.add(StatusEffect::NightVision, 5.0);
+        effects.add(StatusEffect::NightVision, 2.0);
+        assert_eq!(effects.active().len(), 1);
+        assert_eq!(effects.active()[0].remaining, 5.0);
+
+        effects.add(StatusEffect::NightVision, 8.0);
+        assert_eq!(effects.active()[0].remaining, 8.0);
+    }
+
+    #[test]
+    fn different_effects_stack() {
+        let mut effects = StatusEffects::new(Vec::new());
+        effects.add(StatusEffect::Speed, 5.0);
+        effects.add(StatusEffect::Slowness, 5.0);
+        let expected = StatusEffects::SPEED_MULTIPLIER * StatusEffects::SLOWNESS_MULTIPLIER;
+        assert_eq!(effects.speed_multiplier(), expected);
+    }
+}
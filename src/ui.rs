@@ -0,0 +1,312 @@
+//! A small retained widget layer for game menus, drawn with the overlay.
+//! A panel stacks its widgets top to bottom. The focused widget takes
+//! keyboard input, and the mouse focuses whatever it's over.
+
+use winit::keyboard::KeyCode;
+
+use crate::overlay::Overlay;
+
+pub enum WidgetKind {
+    Button,
+    /// A value stepped with left and right, or dragged with the mouse.
+    Slider { value: f32, min: f32, max: f32, step: f32 },
+    /// A scrolling list with one selected row.
+    List { items: Vec<String>, selected: usize, scroll: usize },
+}
+
+pub struct Widget {
+    pub label: String,
+    pub kind: WidgetKind,
+}
+
+impl Widget {
+    pub fn button(label: impl Into<String>) -> Self {
+        Self { label: label.into(), kind: WidgetKind::Button }
+    }
+
+    pub fn slider(label: impl Into<String>, value: f32, min: f32, max: f32, step: f32) -> Self {
+        Self { label: label.into(), kind: WidgetKind::Slider { value, min, max, step } }
+    }
+
+    pub fn list(label: impl Into<String>, items: Vec<String>) -> Self {
+        Self { label: label.into(), kind: WidgetKind::List { items, selected: 0, scroll: 0 } }
+    }
+
+    fn height(&self) -> f32 {
+        match self.kind {
+            WidgetKind::List { .. } => Panel::ROW_HEIGHT * (Panel::LIST_ROWS + 1) as f32,
+            _ => Panel::ROW_HEIGHT,
+        }
+    }
+}
+
+/// Something the player did to the widget at an index.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UiEvent {
+    /// A button was pressed.
+    Pressed(usize),
+    /// A slider or list changed.
+    Changed(usize),
+}
+
+/// A titled, framed column of widgets in the middle of the screen.
+pub struct Panel {
+    pub title: String,
+    pub widgets: Vec<Widget>,
+    focused: usize,
+    /// The last cursor position, in pixels.
+    cursor: Option<(f32, f32)>,
+    /// The slider being dragged, if any.
+    dragging: Option<usize>,
+}
+
+impl Panel {
+    const WIDTH: f32 = 560.0;
+    const ROW_HEIGHT: f32 = 40.0;
+    const SPACING: f32 = 8.0;
+    const PADDING: f32 = 24.0;
+    const BORDER: f32 = 3.0;
+    const TEXT_SCALE: f32 = 3.0;
+    const TITLE_SCALE: f32 = 6.0;
+    /// List rows are smaller, to fit longer items.
+    const LIST_TEXT_SCALE: f32 = 2.0;
+    /// Rows a list shows at once.
+    const LIST_ROWS: usize = 5;
+
+    pub fn new(title: impl Into<String>, widgets: Vec<Widget>) -> Self {
+        Self { title: title.into(), widgets, focused: 0, cursor: None, dragging: None }
+    }
+
+    pub fn focus(&mut self, i: usize) {
+        self.focused = i.min(self.widgets.len().saturating_sub(1));
+    }
+
+    /// The value of the slider at `i`, or 0 if it isn't one.
+    pub fn value(&self, i: usize) -> f32 {
+        match self.widgets[i].kind {
+            WidgetKind::Slider { value, .. } => value,
+            _ => 0.0,
+        }
+    }
+
+    /// Sets the slider at `i`, if it is one.
+    pub fn set_value(&mut self, i: usize, new_value: f32) {
+        if let WidgetKind::Slider { value, min, max, .. } = &mut self.widgets[i].kind {
+            *value = new_value.clamp(*min, *max);
+        }
+    }
+
+    /// The top of the panel, which is centered on the screen unless it's
+    /// too tall to fit.
+    fn top(&self, size: winit::dpi::PhysicalSize<u32>) -> f32 {
+        let widgets: f32 = self.widgets.iter().map(|widget| widget.height() + Self::SPACING).sum();
+        let height = Overlay::line_height(Self::TITLE_SCALE) + Self::PADDING * 3.0 + widgets - Self::SPACING;
+        ((size.height as f32 - height) / 2.0).max(0.0)
+    }
+
+    /// The top-left corner of each widget, with the title above the first.
+    fn layout(&self, size: winit::dpi::PhysicalSize<u32>) -> Vec<(f32, f32)> {
+        let x = (size.width as f32 - Self::WIDTH) / 2.0;
+        let mut y = self.top(size) + Overlay::line_height(Self::TITLE_SCALE) + Self::PADDING * 2.0;
+        self.widgets.iter().map(|widget| {
+            let position = (x, y);
+            y += widget.height() + Self::SPACING;
+            position
+        }).collect()
+    }
+
+    /// The widget under a point, if any.
+    fn widget_at(&self, x: f32, y: f32, size: winit::dpi::PhysicalSize<u32>) -> Option<usize> {
+        self.layout(size).into_iter().zip(&self.widgets).position(|((left, top), widget)| {
+            (left..left + Self::WIDTH).contains(&x) && (top..top + widget.height()).contains(&y)
+        })
+    }
+
+    /// The widget after or before `focused`, wrapping around.
+    fn next_focus(focused: usize, count: usize, forwards: bool) -> usize {
+        if forwards { (focused + 1) % count } else { (focused + count - 1) % count }
+    }
+
+    fn move_focus(&mut self, forwards: bool) {
+        self.focused = Self::next_focus(self.focused, self.widgets.len(), forwards);
+    }
+
+    /// Handles a key press.
+    pub fn key(&mut self, key: KeyCode) -> Option<UiEvent> {
+        let (i, count) = (self.focused, self.widgets.len());
+        match (&mut self.widgets[i].kind, key) {
+            (WidgetKind::List { items, selected, scroll }, KeyCode::ArrowUp | KeyCode::ArrowDown) => {
+                // Moving past either end of the list leaves it
+                let up = key == KeyCode::ArrowUp;
+                if (up && *selected == 0) || (!up && *selected + 1 >= items.len()) {
+                    self.focused = Self::next_focus(i, count, !up);
+                    return None;
+                }
+                *selected = if up { *selected - 1 } else { *selected + 1 };
+                *scroll = (*scroll).clamp(selected.saturating_sub(Self::LIST_ROWS - 1), *selected);
+                Some(UiEvent::Changed(i))
+            }
+            (WidgetKind::Slider { value, min, max, step }, KeyCode::ArrowLeft | KeyCode::ArrowRight | KeyCode::KeyA | KeyCode::KeyD) => {
+                let direction = if matches!(key, KeyCode::ArrowLeft | KeyCode::KeyA) { -1.0 } else { 1.0 };
+                *value = (*value + *step * direction).clamp(*min, *max);
+                Some(UiEvent::Changed(i))
+            }
+            (WidgetKind::Button, KeyCode::Enter | KeyCode::Space) => Some(UiEvent::Pressed(i)),
+            (_, KeyCode::ArrowUp | KeyCode::KeyW) => {
+                self.move_focus(false);
+                None
+            }
+            (_, KeyCode::ArrowDown | KeyCode::KeyS | KeyCode::Tab) => {
+                self.move_focus(true);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Focuses the widget under the cursor, and moves a dragged slider.
+    pub fn cursor_moved(&mut self, x: f32, y: f32, size: winit::dpi::PhysicalSize<u32>) -> Option<UiEvent> {
+        self.cursor = Some((x, y));
+        if let Some(i) = self.dragging {
+            return self.drag_slider(i, x, size);
+        }
+        if let Some(i) = self.widget_at(x, y, size) {
+            self.focused = i;
+        }
+        None
+    }
+
+    /// Presses a button, starts dragging a slider or picks a list row.
+    pub fn mouse_down(&mut self, size: winit::dpi::PhysicalSize<u32>) -> Option<UiEvent> {
+        let (x, y) = self.cursor?;
+        let i = self.widget_at(x, y, size)?;
+        self.focused = i;
+        let top = self.layout(size)[i].1;
+        match &mut self.widgets[i].kind {
+            WidgetKind::Button => Some(UiEvent::Pressed(i)),
+            WidgetKind::Slider { .. } => {
+                self.dragging = Some(i);
+                self.drag_slider(i, x, size)
+            }
+            WidgetKind::List { items, selected, scroll } => {
+                // The first row is the label
+                let row = ((y - top) / Self::ROW_HEIGHT) as usize;
+                let item = (row > 0).then(|| *scroll + row - 1).filter(|&item| item < items.len())?;
+                *selected = item;
+                Some(UiEvent::Changed(i))
+            }
+        }
+    }
+
+    pub fn mouse_up(&mut self) {
+        self.dragging = None;
+    }
+
+    /// Scrolls the list under the cursor by `lines` rows, positive being up.
+    pub fn scroll(&mut self, lines: f32, size: winit::dpi::PhysicalSize<u32>) {
+        let Some(i) = self.cursor.and_then(|(x, y)| self.widget_at(x, y, size)) else {
+            return;
+        };
+        if let WidgetKind::List { items, scroll, .. } = &mut self.widgets[i].kind {
+            let last = items.len().saturating_sub(Self::LIST_ROWS);
+            *scroll = (*scroll as f32 - lines).round().clamp(0.0, last as f32) as usize;
+        }
+    }
+
+    fn drag_slider(&mut self, i: usize, x: f32, size: winit::dpi::PhysicalSize<u32>) -> Option<UiEvent> {
+        let left = self.layout(size)[i].0;
+        let WidgetKind::Slider { value, min, max, step } = &mut self.widgets[i].kind else {
+            return None;
+        };
+        let (track_x, track_width) = Self::slider_track(left);
+        let t = ((x - track_x) / track_width).clamp(0.0, 1.0);
+        let steps = ((*max - *min) * t / *step).round();
+        let new_value = (*min + steps * *step).clamp(*min, *max);
+        if new_value == *value {
+            return None;
+        }
+        *value = new_value;
+        Some(UiEvent::Changed(i))
+    }
+
+    /// The slider track's x position and width; it fills the right 40% of
+    /// the widget, leaving the rest for the label.
+    fn slider_track(left: f32) -> (f32, f32) {
+        (left + Self::WIDTH * 0.6, Self::WIDTH * 0.4 - 16.0)
+    }
+
+    pub fn draw(&self, overlay: &mut Overlay, size: winit::dpi::PhysicalSize<u32>) {
+        let layout = self.layout(size);
+        let (Some(&(left, _)), Some(last)) = (layout.first(), self.widgets.last()) else {
+            return;
+        };
+        let top = self.top(size);
+        let bottom = layout[layout.len() - 1].1 + last.height();
+        Self::frame(overlay, left - Self::PADDING, top, Self::WIDTH + Self::PADDING * 2.0, bottom - top + Self::PADDING);
+
+        // Glyphs are square, so a character is as wide as a line is high
+        let title_width = self.title.len() as f32 * Overlay::line_height(Self::TITLE_SCALE);
+        overlay.text(
+            (size.width as f32 - title_width) / 2.0, top + Self::PADDING,
+            &self.title, Self::TITLE_SCALE, [1.0, 1.0, 1.0, 1.0]
+        );
+
+        for (i, (widget, &(x, y))) in self.widgets.iter().zip(&layout).enumerate() {
+            self.draw_widget(overlay, widget, x, y, i == self.focused);
+        }
+    }
+
+    /// A filled box with a lighter border, standing in for a nine-slice
+    /// panel until the overlay can draw textures.
+    fn frame(overlay: &mut Overlay, x: f32, y: f32, width: f32, height: f32) {
+        let border = [0.5, 0.55, 0.7, 0.9];
+        overlay.rect(x, y, width, height, [0.03, 0.03, 0.06, 0.8]);
+        overlay.rect(x, y, width, Self::BORDER, border);
+        overlay.rect(x, y + height - Self::BORDER, width, Self::BORDER, border);
+        overlay.rect(x, y, Self::BORDER, height, border);
+        overlay.rect(x + width - Self::BORDER, y, Self::BORDER, height, border);
+    }
+
+    fn draw_widget(&self, overlay: &mut Overlay, widget: &Widget, x: f32, y: f32, focused: bool) {
+        let white = [1.0, 1.0, 1.0, 1.0];
+        let background = if focused { [0.25, 0.45, 0.75, 0.9] } else { [0.1, 0.1, 0.15, 0.8] };
+        let line_height = Overlay::line_height(Self::TEXT_SCALE);
+        let text_y = y + (Self::ROW_HEIGHT - line_height) / 2.0;
+        overlay.rect(x, y, Self::WIDTH, Self::ROW_HEIGHT, background);
+
+        match &widget.kind {
+            WidgetKind::Button => {
+                let text_x = x + (Self::WIDTH - widget.label.len() as f32 * line_height) / 2.0;
+                overlay.text(text_x, text_y, &widget.label, Self::TEXT_SCALE, white);
+            }
+            WidgetKind::Slider { value, min, max, .. } => {
+                overlay.text(x + 12.0, text_y, &widget.label, Self::TEXT_SCALE, white);
+                let (track_x, track_width) = Self::slider_track(x);
+                let t = if max > min { (value - min) / (max - min) } else { 0.0 };
+                let track_y = y + Self::ROW_HEIGHT / 2.0 - 3.0;
+                overlay.rect(track_x, track_y, track_width, 6.0, [0.3, 0.3, 0.4, 1.0]);
+                overlay.rect(track_x, track_y, track_width * t, 6.0, [0.5, 0.75, 1.0, 1.0]);
+                overlay.rect(track_x + track_width * t - 5.0, y + 8.0, 10.0, Self::ROW_HEIGHT - 16.0, white);
+            }
+            WidgetKind::List { items, selected, scroll } => {
+                overlay.text(x + 12.0, text_y, &widget.label, Self::TEXT_SCALE, white);
+                let rows = items.iter().enumerate().skip(*scroll).take(Self::LIST_ROWS);
+                let row_line_height = Overlay::line_height(Self::LIST_TEXT_SCALE);
+                let max_chars = ((Self::WIDTH - 24.0) / row_line_height) as usize;
+                for (row, (item_index, item)) in rows.enumerate() {
+                    let row_y = y + Self::ROW_HEIGHT * (row + 1) as f32;
+                    let color = if item_index == *selected { [0.2, 0.35, 0.6, 0.9] } else { [0.05, 0.05, 0.1, 0.8] };
+                    overlay.rect(x, row_y, Self::WIDTH, Self::ROW_HEIGHT, color);
+                    // Items too long for the row are cut short
+                    let text = if item.chars().count() > max_chars {
+                        format!("{}...", item.chars().take(max_chars - 3).collect::<String>())
+                    } else {
+                        item.clone()
+                    };
+                    let text_y = row_y + (Self::ROW_HEIGHT - row_line_height) / 2.0;
+                    overlay.text(x + 12.0, text_y, &text, Self::LIST_TEXT_SCALE, white);
+                }
+            }
+        }
+    }
+}
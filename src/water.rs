@@ -0,0 +1,372 @@
+//! Water has its own forward pass instead of going through the G-buffer, so
+//! it can see what's behind its surface. It's drawn into the lit scene after
+//! the lighting pass, reading a copy of the scene for refraction and the
+//! G-buffer depth for absorption and shoreline foam.
+
+use std::{mem, sync::Arc};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    memory::MemoryUsage, model::Vertex, pipeline_cache::PipelineCache, render_cache::RenderCache, settings::ReflectionMode, shader_preprocessor::ShaderDefines,
+    texture::Texture
+};
+
+/// Half the width of the square of water, in world units.
+const HALF_SIZE: f32 = 64.0;
+/// Quads along each side of the water mesh. Waves are displaced per vertex,
+/// so this limits how small they can be.
+const RESOLUTION: u32 = 128;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct WaterVertex {
+    /// x and z; the height comes from the water level.
+    position: [f32; 2],
+}
+
+impl WaterVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x2];
+}
+
+impl Vertex for WaterVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<WaterVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &WaterVertex::ATTRIBS
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct WaterUniform {
+    time: f32,
+    level: f32,
+    max_steps: u32,
+    refine_steps: u32,
+}
+
+pub struct Water {
+    /// Height of the water's surface when it's still.
+    pub level: f32,
+    time: f32,
+
+    pipelines: PipelineCache,
+    /// The shader variant for the current reflection mode.
+    defines: ShaderDefines,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    planar_sampler: wgpu::Sampler,
+    /// A copy of the lit scene before water is drawn into it.
+    refraction_texture: Texture,
+}
+
+impl Water {
+    /// `gbuf_layout`, `camera_layout` and `sky_layout` are bound at groups 0-2
+    /// when drawing; the water's own resources go in group 3. Only the variant
+    /// reflecting the sky is compiled up front; `prepare` picks the variant to
+    /// use, and others compile in the background when they're first needed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        cache: &RenderCache,
+        config: &wgpu::SurfaceConfiguration,
+        level: f32,
+        gbuf_layout: &wgpu::BindGroupLayout,
+        camera_layout: &wgpu::BindGroupLayout,
+        sky_layout: &wgpu::BindGroupLayout,
+        planar_texture: &Texture
+    ) -> anyhow::Result<Self> {
+        let (vertices, indices) = Self::grid();
+        let vertex_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Water Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            }
+        );
+        let index_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Water Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            }
+        );
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Water Uniform Buffer"),
+            size: mem::size_of::<WaterUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Water Bind Group Layout"),
+            entries: &[
+                // 0: settings
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // 1: scene without water
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                // 2: planar reflection
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // 3: planar reflection sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                }
+            ]
+        });
+        // The planar reflection is lower resolution than the screen, so filter it
+        let planar_sampler = cache.linear_clamp_sampler();
+        let refraction_texture = Texture::create_render_target(device, config, "refraction_texture", Texture::HDR_FORMAT);
+        let bind_group = Self::create_bind_group(
+            device, &bind_group_layout, &uniform_buffer, &planar_sampler, &refraction_texture, planar_texture
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Water Pipeline Layout"),
+            bind_group_layouts: &[gbuf_layout, camera_layout, sky_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipelines = PipelineCache::new(
+            device,
+            "Water Shader",
+            include_str!("shaders/waterShader.wgsl"),
+            ShaderDefines::new(),
+            Arc::new(move |device, shader| Self::create_pipeline(device, &pipeline_layout, shader))
+        )?;
+
+        Ok(Self {
+            level,
+            time: 0.0,
+            pipelines,
+            defines: ShaderDefines::new(),
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            planar_sampler,
+            refraction_texture,
+        })
+    }
+
+    /// The shader variant for a reflection mode.
+    fn defines(reflections: ReflectionMode) -> ShaderDefines {
+        ShaderDefines::new()
+            .set("PLANAR_REFLECTIONS", reflections == ReflectionMode::Planar)
+            .set("SSR_REFLECTIONS", matches!(reflections, ReflectionMode::SsrLow | ReflectionMode::SsrHigh))
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Water Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    WaterVertex::desc()
+                ],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: Texture::HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // Visible from underwater too
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            // The G-buffer depth is read in the shader, so it can't also be attached
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None
+        })
+    }
+
+    /// A flat square grid of vertices centered on the origin.
+    fn grid() -> (Vec<WaterVertex>, Vec<u32>) {
+        let step = HALF_SIZE * 2.0 / RESOLUTION as f32;
+        let vertices = (0..=RESOLUTION)
+            .flat_map(|z| (0..=RESOLUTION).map(move |x| WaterVertex {
+                position: [x as f32 * step - HALF_SIZE, z as f32 * step - HALF_SIZE],
+            }))
+            .collect();
+
+        let row = RESOLUTION + 1;
+        let indices = (0..RESOLUTION)
+            .flat_map(|z| (0..RESOLUTION).flat_map(move |x| {
+                let i = z * row + x;
+                [i, i + row, i + row + 1, i, i + row + 1, i + 1]
+            }))
+            .collect();
+        (vertices, indices)
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        planar_sampler: &wgpu::Sampler,
+        refraction_texture: &Texture,
+        planar_texture: &Texture
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&refraction_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&planar_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(planar_sampler),
+                }
+            ],
+            label: Some("Water Bind Group"),
+        })
+    }
+
+    /// Recreates the refraction copy at the new size and points the pass at
+    /// the new planar reflection texture.
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, planar_texture: &Texture) {
+        self.refraction_texture = Texture::create_render_target(device, config, "refraction_texture", Texture::HDR_FORMAT);
+        self.bind_group = Self::create_bind_group(
+            device, &self.bind_group_layout, &self.uniform_buffer, &self.planar_sampler, &self.refraction_texture, planar_texture
+        );
+    }
+
+    pub fn memory(&self) -> MemoryUsage {
+        MemoryUsage {
+            vertices: self.vertex_buffer.size() / mem::size_of::<WaterVertex>() as u64,
+            indices: self.num_indices as u64,
+            ..MemoryUsage::buffer(&self.vertex_buffer)
+                + MemoryUsage::buffer(&self.index_buffer)
+                + MemoryUsage::buffer(&self.uniform_buffer)
+                + MemoryUsage::texture(&self.refraction_texture.texture)
+        }
+    }
+
+    /// Advances the waves.
+    pub fn update(&mut self, delta_time: f32) {
+        self.time += delta_time;
+    }
+
+    pub fn prepare(&mut self, queue: &wgpu::Queue, reflections: ReflectionMode) {
+        self.defines = Self::defines(reflections);
+        let (max_steps, refine_steps) = reflections.ssr_steps();
+        let uniform = WaterUniform {
+            time: self.time,
+            level: self.level,
+            max_steps,
+            refine_steps,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    /// Draws water into `scene_texture`, which must hold the lit scene.
+    pub fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        scene_texture: &Texture,
+        gbuf_bind_group: &wgpu::BindGroup,
+        camera_bind_group: &wgpu::BindGroup,
+        sky_bind_group: &wgpu::BindGroup
+    ) {
+        // The water shader reads the scene behind it, so it can't read the
+        // texture it's drawing into
+        encoder.copy_texture_to_texture(
+            scene_texture.texture.as_image_copy(),
+            self.refraction_texture.texture.as_image_copy(),
+            scene_texture.texture.size()
+        );
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Water Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &scene_texture.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(self.pipelines.get(&self.defines));
+        render_pass.set_bind_group(0, gbuf_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, sky_bind_group, &[]);
+        render_pass.set_bind_group(3, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    }
+}